@@ -0,0 +1,423 @@
+//! The AUR: RPC v5 records, the [`AurSource`] interface and its HTTP client.
+//!
+//! Info lookups are batched into as few `arg[]=` queries as the URL length
+//! allows, memoized for the lifetime of the process (including "not found"),
+//! and optionally persisted to a short-TTL on-disk cache. Only packages that
+//! exist go to disk, so one published after a failed lookup is seen next run.
+
+use anyhow::{Context, Result, bail};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::error::YaoError;
+use crate::fetch::{self, Fetched};
+
+/* ---------------------- RPC models ---------------------- */
+
+#[derive(Deserialize, Debug)]
+struct AurInfoResponse {
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(default)]
+    error: Option<String>,
+    results: Option<Vec<AurPkg>>,
+}
+
+/// One package from an RPC v5 info/search response.
+/// search results only carry the scalar fields; the arrays stay empty there.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AurPkg {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "PackageBase", default)]
+    pub package_base: Option<String>,
+    #[serde(rename = "Version", default)]
+    pub version: String,
+    #[serde(rename = "Description", default)]
+    pub description: Option<String>,
+    #[serde(rename = "URL", default)]
+    pub url: Option<String>,
+    #[serde(rename = "Maintainer", default)]
+    pub maintainer: Option<String>,
+    #[serde(rename = "NumVotes", default)]
+    pub num_votes: u32,
+    #[serde(rename = "Popularity", default)]
+    pub popularity: f64,
+    /// Unix time the package was flagged, if flagged
+    #[serde(rename = "OutOfDate", default)]
+    pub out_of_date: Option<i64>,
+    #[serde(rename = "FirstSubmitted", default)]
+    pub first_submitted: i64,
+    #[serde(rename = "LastModified", default)]
+    pub last_modified: i64,
+    #[serde(rename = "Depends", default)]
+    pub depends: Vec<String>,
+    #[serde(rename = "MakeDepends", default)]
+    pub make_depends: Vec<String>,
+    #[serde(rename = "CheckDepends", default)]
+    pub check_depends: Vec<String>,
+    #[serde(rename = "OptDepends", default)]
+    pub opt_depends: Vec<String>,
+    #[serde(rename = "Conflicts", default)]
+    pub conflicts: Vec<String>,
+    #[serde(rename = "Provides", default)]
+    pub provides: Vec<String>,
+    #[serde(rename = "Replaces", default)]
+    pub replaces: Vec<String>,
+    #[serde(rename = "License", default)]
+    pub license: Vec<String>,
+    #[serde(rename = "Keywords", default)]
+    pub keywords: Vec<String>,
+}
+
+/* ---------------------- Source ---------------------- */
+
+/// Where AUR package records and build trees come from.
+///
+/// Lookups default to `multiinfo`; [`AurClient`] batches and memoizes them.
+pub trait AurSource {
+    /// Info records for several packages; names not in the AUR are left out.
+    fn multiinfo(&self, names: &[String]) -> Result<Vec<AurPkg>>;
+
+    /// Search by name and description (results lack dependency arrays).
+    fn search(&self, term: &str) -> Result<Vec<AurPkg>>;
+
+    /// Packages whose `provides` (or name) matches `name`.
+    fn search_provides(&self, name: &str) -> Result<Vec<AurPkg>>;
+
+    /// Fetch the build tree of the pkgbase that builds `name` into
+    /// `dest/{pkgbase}`.
+    fn fetch(&self, cfg: &Config, name: &str, dest: &Path) -> Result<Fetched>;
+
+    /// Look up `names` ahead of the `info` calls that follow.
+    fn prefetch(&self, names: &[String]) -> Result<()> {
+        self.multiinfo(names).map(drop)
+    }
+
+    /// Info record for a single package (None if it does not exist).
+    fn info(&self, name: &str) -> Result<Option<AurPkg>> {
+        let found = self.multiinfo(&[name.to_string()])?;
+        Ok(found.into_iter().find(|p| p.name == name))
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.info(name)?.is_some())
+    }
+
+    /// The pkgbase `name` is built from (the name itself if unknown).
+    fn pkgbase(&self, name: &str) -> Result<String> {
+        Ok(self
+            .info(name)?
+            .and_then(|p| p.package_base)
+            .unwrap_or_else(|| name.to_string()))
+    }
+}
+
+/* ---------------------- RPC client ---------------------- */
+
+/// The AUR rejects request URIs much beyond 4443 bytes; stay below that.
+const MAX_URL_LEN: usize = 4000;
+
+/// RPC v5 endpoint, relative to the configured `aur_url`.
+const RPC_PATH: &str = "/rpc/?v=5";
+
+pub struct AurClient {
+    http: Client,
+    /// `{aur_url}/rpc/?v=5`
+    rpc_url: String,
+    /// name -> info (None = known not to exist)
+    memo: RefCell<HashMap<String, Option<AurPkg>>>,
+    disk: Option<DiskCache>,
+    verbose: bool,
+}
+
+struct DiskCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl DiskCache {
+    /// `{dir}/{name}.json`; None for anything that is not a valid pkgname,
+    /// so a target like `../../etc/x` never becomes a path.
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        valid_pkgname(name).then(|| self.dir.join(format!("{name}.json")))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    /// Unix time of the RPC request
+    fetched: u64,
+    pkg: AurPkg,
+}
+
+impl AurClient {
+    pub fn new(cfg: &Config) -> Result<Self> {
+        let disk = (cfg.rpc_cache_ttl > 0).then(|| DiskCache {
+            dir: cfg
+                .snapshot_cache
+                .parent()
+                .unwrap_or(&cfg.snapshot_cache)
+                .join("rpc"),
+            ttl: Duration::from_secs(cfg.rpc_cache_ttl),
+        });
+        Ok(Self {
+            http: http_client()?,
+            rpc_url: format!("{}{RPC_PATH}", cfg.aur_url),
+            memo: RefCell::new(HashMap::new()),
+            disk,
+            verbose: cfg.verbose,
+        })
+    }
+
+    /// Underlying HTTP client (for snapshot downloads).
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    /// GET the RPC endpoint with URL-encoded query params (names like `gtk+` need it).
+    fn rpc(&self, params: &[(&str, &str)]) -> Result<Vec<AurPkg>> {
+        let url = reqwest::Url::parse_with_params(&self.rpc_url, params)?;
+        if self.verbose {
+            eprintln!("==> AUR RPC {url}");
+        }
+        let resp = self
+            .http
+            .get(url)
+            .send()
+            .context(YaoError::Network("cannot reach the AUR RPC".into()))?;
+        if !resp.status().is_success() {
+            bail!(YaoError::AurRpc(format!(
+                "AUR RPC returned {}",
+                resp.status()
+            )));
+        }
+        let info: AurInfoResponse = resp
+            .json()
+            .context(YaoError::AurRpc("invalid AUR RPC response".into()))?;
+        if info.ty == "error" {
+            bail!(YaoError::AurRpc(format!(
+                "AUR RPC error: {}",
+                info.error.as_deref().unwrap_or("unknown error")
+            )));
+        }
+        Ok(info.results.unwrap_or_default())
+    }
+
+    /// A cached record younger than the TTL.
+    fn disk_get(&self, name: &str) -> Option<AurPkg> {
+        let disk = self.disk.as_ref()?;
+        let data = fs::read(disk.path(name)?).ok()?;
+        let entry: DiskEntry = serde_json::from_slice(&data).ok()?;
+        let age = now_secs().saturating_sub(entry.fetched);
+        (age < disk.ttl.as_secs()).then_some(entry.pkg)
+    }
+
+    /// Best effort: an unwritable cache dir only costs us the cache.
+    fn disk_put(&self, name: &str, pkg: &AurPkg) {
+        let Some(disk) = &self.disk else { return };
+        let Some(path) = disk.path(name) else { return };
+        let entry = DiskEntry {
+            fetched: now_secs(),
+            pkg: pkg.clone(),
+        };
+        let res = fs::create_dir_all(&disk.dir).and_then(|_| {
+            let json = serde_json::to_vec(&entry)?;
+            fs::write(path, json)
+        });
+        if let Err(e) = res
+            && self.verbose
+        {
+            eprintln!("==> could not write RPC cache for {name}: {e}");
+        }
+    }
+}
+
+impl AurSource for AurClient {
+    fn info(&self, name: &str) -> Result<Option<AurPkg>> {
+        self.prefetch(&[name.to_string()])?;
+        Ok(self.memo.borrow().get(name).cloned().flatten())
+    }
+
+    fn multiinfo(&self, names: &[String]) -> Result<Vec<AurPkg>> {
+        self.prefetch(names)?;
+        let memo = self.memo.borrow();
+        Ok(names
+            .iter()
+            .filter_map(|n| memo.get(n).cloned().flatten())
+            .collect())
+    }
+
+    /// Make sure every name is in the memo, querying only what is missing.
+    fn prefetch(&self, names: &[String]) -> Result<()> {
+        let mut missing: Vec<&str> = Vec::new();
+        {
+            let mut memo = self.memo.borrow_mut();
+            for n in names {
+                if memo.contains_key(n) || missing.contains(&n.as_str()) {
+                    continue;
+                }
+                match self.disk_get(n) {
+                    Some(pkg) => {
+                        memo.insert(n.clone(), Some(pkg));
+                    }
+                    None => missing.push(n),
+                }
+            }
+        }
+
+        for chunk in batches(self.rpc_url.len(), &missing) {
+            let mut params = vec![("type", "info")];
+            params.extend(chunk.iter().map(|n| ("arg[]", *n)));
+            let found = self.rpc(&params)?;
+
+            let mut memo = self.memo.borrow_mut();
+            for n in chunk {
+                let pkg = found.iter().find(|p| p.name == *n).cloned();
+                if let Some(pkg) = &pkg {
+                    self.disk_put(n, pkg);
+                }
+                memo.insert(n.to_string(), pkg);
+            }
+        }
+        Ok(())
+    }
+
+    /// Not memoized: search results lack dependency arrays.
+    fn search(&self, term: &str) -> Result<Vec<AurPkg>> {
+        self.rpc(&[("type", "search"), ("arg", term)])
+    }
+
+    fn search_provides(&self, name: &str) -> Result<Vec<AurPkg>> {
+        self.rpc(&[("type", "search"), ("by", "provides"), ("arg", name)])
+    }
+
+    fn fetch(&self, cfg: &Config, name: &str, dest: &Path) -> Result<Fetched> {
+        fetch::fetch(self, cfg, name, dest)
+    }
+}
+
+/// Split names into chunks whose encoded `&arg[]=name` params fit in one URL
+/// after an endpoint of `url_len` bytes.
+fn batches<'a>(url_len: usize, names: &[&'a str]) -> Vec<Vec<&'a str>> {
+    let base = url_len + "&type=info".len();
+    let mut out: Vec<Vec<&str>> = Vec::new();
+    let mut len = base;
+    for &n in names {
+        let cost = "&arg%5B%5D=".len() + encoded_len(n);
+        match out.last_mut() {
+            Some(chunk) if len + cost <= MAX_URL_LEN => chunk.push(n),
+            _ => {
+                out.push(vec![n]);
+                len = base;
+            }
+        }
+        len += cost;
+    }
+    out
+}
+
+/// Upper bound of the percent-encoded length of a query value.
+fn encoded_len(s: &str) -> usize {
+    s.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                1
+            } else {
+                3
+            }
+        })
+        .sum()
+}
+
+fn http_client() -> Result<Client> {
+    let client = Client::builder()
+        .user_agent("yaorust/0.1 (+https://github.com/darko5r/yaorust)")
+        .build()?;
+    Ok(client)
+}
+
+/// The AUR's pkgname rule: `[a-z0-9@._+-]`, not starting with `-` or `.`.
+fn valid_pkgname(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(['-', '.'])
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "@._+-".contains(c))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_respect_url_length() {
+        let names: Vec<String> = (0..400).map(|i| format!("some-package-name-{i}")).collect();
+        let refs: Vec<&str> = names.iter().map(String::as_str).collect();
+        let url = format!("https://aur.archlinux.org{RPC_PATH}");
+        let chunks = batches(url.len(), &refs);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), 400);
+        for c in &chunks {
+            let len: usize = c.iter().map(|n| 11 + encoded_len(n)).sum();
+            assert!(url.len() + 10 + len <= MAX_URL_LEN);
+        }
+        assert_eq!(batches(url.len(), &refs[..40]).len(), 1);
+        assert!(batches(url.len(), &[]).is_empty());
+    }
+
+    #[test]
+    fn disk_cache_serves_fresh_records() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut cfg = Config::default();
+        cfg.rpc_cache_ttl = 60;
+        cfg.snapshot_cache = tmp.path().join("snapshots");
+        let aur = AurClient::new(&cfg).unwrap();
+        let foo: AurPkg = serde_json::from_value(serde_json::json!({"Name": "foo"})).unwrap();
+
+        aur.disk_put("foo", &foo);
+        assert_eq!(aur.disk_get("foo").unwrap().name, "foo");
+
+        assert!(aur.disk_get("bar").is_none());
+
+        // older than the TTL
+        let stale = DiskEntry {
+            fetched: now_secs() - 61,
+            pkg: foo,
+        };
+        fs::write(
+            tmp.path().join("rpc/foo.json"),
+            serde_json::to_vec(&stale).unwrap(),
+        )
+        .unwrap();
+        assert!(aur.disk_get("foo").is_none());
+    }
+
+    #[test]
+    fn disk_cache_only_takes_pkgnames() {
+        let disk = DiskCache {
+            dir: PathBuf::from("/var/cache/yaorust/rpc"),
+            ttl: Duration::from_secs(60),
+        };
+        assert_eq!(
+            disk.path("gtk+-2.0_git@1"),
+            Some(PathBuf::from("/var/cache/yaorust/rpc/gtk+-2.0_git@1.json"))
+        );
+        for name in ["../../etc/x", "a/b", ".hidden", "-opt", "Foo", ""] {
+            assert_eq!(disk.path(name), None, "{name}");
+        }
+    }
+}
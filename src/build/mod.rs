@@ -0,0 +1,408 @@
+//! Building AUR packages: the [`Builder`] interface and the makepkg-based
+//! default, plus how makepkg is run as root (user, sandbox, trust-root).
+
+pub mod runner;
+pub mod sandbox;
+pub mod srcinfo;
+pub mod trustroot;
+pub mod usermode;
+
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+use crate::aur::AurSource;
+use crate::config::Config;
+use crate::conflicts::Removal;
+use crate::deps::BaseBuild;
+use crate::error::YaoError;
+use crate::is_root;
+use crate::pacman::{self, PackageDb, db::PacmanDb};
+use crate::ui::review;
+use crate::ui::{run_command_printing, run_pacman};
+use runner::Runner;
+use srcinfo::{MakepkgConf, SrcInfo};
+
+/// Turns one pkgbase of a build plan into installed packages.
+pub trait Builder {
+    /// Build one pkgbase and install the plan members it produces (plus any
+    /// other split packages the user picks). `db` is the system as it was
+    /// before the run; earlier builds of the plan may have installed more.
+    fn build(
+        &self,
+        cfg: &Config,
+        aur: &dyn AurSource,
+        db: &dyn PackageDb,
+        build: &BaseBuild,
+        removals: &[Removal],
+        force: bool,
+    ) -> Result<()>;
+}
+
+/// Fetch, review, `makepkg`, then `pacman -U`.
+pub struct MakepkgBuilder;
+
+impl Builder for MakepkgBuilder {
+    fn build(
+        &self,
+        cfg: &Config,
+        aur: &dyn AurSource,
+        _db: &dyn PackageDb,
+        build: &BaseBuild,
+        removals: &[Removal],
+        force: bool,
+    ) -> Result<()> {
+        let name = build.pkgbase.as_str();
+        // [package.<pkgbase>] settings from the config files
+        let cfg = &cfg.for_package(name);
+
+        // 1) Fetch & extract
+        let tmp = TempDir::new()?;
+        let fetched = aur.fetch(cfg, &build.members[0].name, tmp.path())?;
+        if let Some(commit) = &fetched.commit {
+            eprintln!("==> Building {name} at commit {commit}");
+        }
+        let build_dir = fetched.dir;
+        if !build_dir.is_dir() {
+            bail!(YaoError::Extract(format!(
+                "unexpected snapshot layout for {name}"
+            )));
+        }
+
+        // 1.5) Review: full PKGBUILD the first time, a diff since the last approval after that
+        if !review::review(cfg, name, &build_dir)? {
+            bail!(YaoError::Aborted);
+        }
+
+        // 1.75) Decide who runs makepkg and hand the work dir over if needed
+        let runner = Runner::select(cfg)
+            .with_context(|| YaoError::Permission(format!("cannot build {name} as root")))?;
+        let build_pkgdest = runner.prepare(tmp.path(), &cfg.pkgdest)?;
+
+        // 2) Resolve exact outputs from .SRCINFO (no PKGBUILD is sourced for this)
+        let srcinfo = SrcInfo::read(&build_dir)?;
+        let conf = MakepkgConf::load(runner.user_makepkg_conf());
+        if !srcinfo.arch.iter().any(|a| a == "any" || *a == conf.carch) {
+            bail!(
+                "{name} is not available for {} (arch: {})",
+                conf.carch,
+                srcinfo.arch.join(" ")
+            );
+        }
+        let targets: Vec<PathBuf> = srcinfo
+            .packages
+            .iter()
+            .map(|p| srcinfo.package_file(p, &conf, &cfg.pkgdest))
+            .collect();
+        // Written only with OPTIONS=(debug strip) and if there were symbols
+        let debug: Option<(String, PathBuf)> = srcinfo.debug_package(&conf).map(|d| {
+            let file = srcinfo.package_file(&d, &conf, &cfg.pkgdest);
+            (d.pkgname, file)
+        });
+        if cfg.verbose {
+            for t in targets.iter().chain(debug.as_ref().map(|(_, f)| f)) {
+                eprintln!("==> target {}", t.display());
+            }
+        }
+
+        // 2.5) Only the requested split packages get installed; offer the rest
+        for m in &build.members {
+            if !srcinfo.packages.iter().any(|p| p.pkgname == m.name) {
+                bail!(
+                    "{} is not built by {name} (pkgname missing from .SRCINFO)",
+                    m.name
+                );
+            }
+        }
+        let others: Vec<&str> = srcinfo
+            .packages
+            .iter()
+            .map(|p| p.pkgname.as_str())
+            .filter(|p| !build.members.iter().any(|m| m.name == *p))
+            .collect();
+        let mut extra: Vec<&str> = Vec::new();
+        if !others.is_empty() {
+            eprintln!(":: {name} also builds {} other package(s):", others.len());
+            for (i, p) in others.iter().enumerate() {
+                eprintln!("   {}) {p}", i + 1);
+            }
+            extra = prompt_extras(cfg, others.len())?
+                .into_iter()
+                .map(|i| others[i])
+                .collect();
+        }
+
+        // 3) Force handling (remove previous artifacts when -f)
+        if force {
+            for file in targets.iter().chain(debug.as_ref().map(|(_, f)| f)) {
+                if file.exists() {
+                    if cfg.verbose {
+                        eprintln!("==> removing {}", file.display());
+                    }
+                    let _ = fs::remove_file(file);
+                }
+                let local =
+                    build_dir.join(file.file_name().expect("package filename should exist"));
+                if local.exists() {
+                    let _ = fs::remove_file(local);
+                }
+            }
+        }
+
+        // If all target files already exist and NOT forcing, skip rebuild
+        let all_exist = targets.iter().all(|t| t.exists());
+        if !force && all_exist {
+            if cfg.verbose {
+                eprintln!("==> Using existing package file(s) for {name}, skipping rebuild");
+            }
+        } else {
+            // 4) Build with makepkg (as current EUID, or as build_user when root)
+            if !runner.can_syncdeps() {
+                // re-read: AUR deps built earlier in this run are installed now
+                let db = PacmanDb::open(cfg)?;
+                install_repo_deps(cfg, &db, &srcinfo, &conf.carch)?;
+            }
+
+            if let Some(mut fetch) =
+                runner.fetch_sources(&cfg.makepkg, &build_dir, &build_pkgdest)?
+            {
+                eprintln!("==> Downloading sources for {name}...");
+                run_command_printing(&mut fetch, cfg.verbose).with_context(|| {
+                    YaoError::Build(format!("cannot download the sources of {name}"))
+                })?;
+            }
+
+            let mut mk = runner.makepkg(&cfg.makepkg, &build_dir, &build_pkgdest)?;
+            mk.arg("--clean").arg("--cleanbuild");
+            if runner.can_syncdeps() {
+                mk.arg("--syncdeps");
+            }
+            if cfg.noconfirm {
+                mk.arg("--noconfirm");
+            }
+            mk.arg("--needed")
+                .arg("--log")
+                .arg("--config")
+                .arg("/etc/makepkg.conf");
+
+            if force {
+                mk.arg("-f").arg("-C");
+            }
+
+            eprintln!("==> Building {name} {} (makepkg)...", srcinfo.version());
+            run_command_printing(&mut mk, cfg.verbose)
+                .with_context(|| YaoError::Build(format!("makepkg failed for {name}")))?;
+
+            // 5) Ensure artifacts exist (staged builds or PKGBUILDs dropping in CWD → move to PKGDEST)
+            for target in targets.iter().chain(debug.as_ref().map(|(_, f)| f)) {
+                if !target.exists() {
+                    let file_name = target.file_name().expect("package filename should exist");
+                    for dir in [&build_pkgdest, &build_dir] {
+                        let local = dir.join(file_name);
+                        if local.exists() {
+                            move_file(&local, target)?;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        // 6) Install via pacman -U (pacman shows details + its own prompt)
+        let file_of = |pkg: &str| {
+            let i = srcinfo
+                .packages
+                .iter()
+                .position(|p| p.pkgname == pkg)
+                .expect("member checked against .SRCINFO");
+            &targets[i]
+        };
+        let mut install: Vec<(&str, &PathBuf, bool)> = build
+            .members
+            .iter()
+            .map(|m| (m.name.as_str(), file_of(&m.name), m.as_dep))
+            .chain(extra.iter().map(|p| (*p, file_of(p), false)))
+            .collect();
+        let deps_only = install.iter().all(|(_, _, as_dep)| *as_dep);
+        if let Some((pkg, file)) = &debug
+            && file.exists()
+        {
+            if cfg.with_debug {
+                install.push((pkg, file, deps_only));
+            } else {
+                eprintln!(
+                    "==> {pkg} kept in {} (not installed, use --with-debug)",
+                    cfg.pkgdest.display()
+                );
+            }
+        }
+
+        let removals: Vec<&Removal> = removals
+            .iter()
+            .filter(|r| install.iter().any(|(pkg, _, _)| *pkg == r.by))
+            .collect();
+
+        let mut pac = pacman::command(cfg);
+        pac.arg("-U");
+        if deps_only {
+            pac.arg("--asdeps");
+        }
+        if removals.iter().any(|r| !r.replaces) {
+            // already confirmed up-front: answer pacman's conflict question with yes
+            pac.arg("--ask=4");
+        }
+        pac.args(install.iter().map(|(_, file, _)| file));
+
+        if !is_root() {
+            pac = pacman::with_sudo(cfg, pac);
+        }
+
+        let names: Vec<&str> = install.iter().map(|(pkg, _, _)| *pkg).collect();
+        eprintln!("==> Installing {}", names.join(" "));
+        // a declined pacman prompt fails like any other pacman error
+        run_pacman(&mut pac, cfg.verbose)?;
+        // what the transaction left installed
+        let db = PacmanDb::open(cfg)?;
+
+        // Mixed explicit/dependency members: one transaction, then fix the reasons
+        let as_deps: Vec<&str> = install
+            .iter()
+            .filter(|(pkg, _, as_dep)| *as_dep && db.is_installed(pkg))
+            .map(|(pkg, _, _)| *pkg)
+            .collect();
+        if !deps_only && !as_deps.is_empty() {
+            let mut mark = Command::new(&cfg.pacman);
+            mark.arg("-D").arg("--asdeps").args(&as_deps);
+            if !is_root() {
+                mark = pacman::with_sudo(cfg, mark);
+            }
+            run_command_printing(&mut mark, cfg.verbose).context(YaoError::Install(
+                "cannot mark packages as dependencies".into(),
+            ))?;
+        }
+
+        // pacman -U does not act on `replaces`; remove what is left over
+        let leftover: Vec<&str> = removals
+            .iter()
+            .filter(|r| db.is_installed(&r.name) && db.is_installed(&r.by))
+            .map(|r| r.name.as_str())
+            .collect();
+        if !leftover.is_empty() {
+            let mut rm = pacman::command(cfg);
+            rm.arg("-R").args(&leftover);
+            if !is_root() {
+                rm = pacman::with_sudo(cfg, rm);
+            }
+            eprintln!("==> Removing replaced package(s): {}", leftover.join(" "));
+            run_pacman(&mut rm, cfg.verbose)?;
+        }
+        Ok(())
+    }
+}
+
+/// Install the repo dependencies of an AUR package ourselves (as root), for
+/// runners where makepkg cannot use `--syncdeps`. AUR deps are already
+/// installed at this point by the resolved build plan.
+fn install_repo_deps(
+    cfg: &Config,
+    db: &dyn PackageDb,
+    srcinfo: &SrcInfo,
+    carch: &str,
+) -> Result<()> {
+    let missing: Vec<&String> = srcinfo
+        .build_depends(carch)
+        .into_iter()
+        .filter(|d| !db.dep_satisfied(d))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let mut cmd = pacman::command(cfg);
+    cmd.arg("-S").arg("--asdeps").arg("--needed").args(missing);
+    eprintln!("==> Installing build dependencies for {}", srcinfo.pkgbase);
+    run_command_printing(&mut cmd, cfg.verbose).with_context(|| {
+        YaoError::Install(format!(
+            "cannot install build dependencies of {}",
+            srcinfo.pkgbase
+        ))
+    })
+}
+
+/// Which of `count` extra split packages to install as well (0-based);
+/// nothing with --noconfirm or an empty answer. Asks again on invalid input.
+fn prompt_extras(cfg: &Config, count: usize) -> Result<Vec<usize>> {
+    if cfg.noconfirm {
+        return Ok(Vec::new());
+    }
+    let mut stdout = io::stdout();
+    loop {
+        write!(
+            stdout,
+            ":: Also install (numbers or ranges, e.g. 1 3 or 1-2; enter for none): "
+        )?;
+        stdout.flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(Vec::new());
+        }
+        match parse_extras(&input, count) {
+            Ok(picked) => return Ok(picked),
+            Err(e) => eprintln!("error: {e}"),
+        }
+    }
+}
+
+/// Explicit numbers and ranges only (`1 3`, `1-2`); no `^` exclusions.
+fn parse_extras(input: &str, count: usize) -> Result<Vec<usize>> {
+    let index = |s: &str| -> Result<usize> {
+        match s.parse::<usize>() {
+            Ok(n) if (1..=count).contains(&n) => Ok(n),
+            _ => bail!("'{s}' is not a number from 1 to {count}"),
+        }
+    };
+    let mut picked: Vec<usize> = Vec::new();
+    for tok in input.split(|c: char| c.is_whitespace() || c == ',') {
+        if tok.is_empty() {
+            continue;
+        }
+        let (lo, hi) = match tok.split_once('-') {
+            Some((a, b)) => (index(a)?, index(b)?),
+            None => (index(tok)?, index(tok)?),
+        };
+        if lo > hi {
+            bail!("invalid range {tok}");
+        }
+        picked.extend(lo - 1..hi);
+    }
+    picked.sort();
+    picked.dedup();
+    Ok(picked)
+}
+
+/// rename(2), falling back to copy + remove across filesystems (tmpfs → /var).
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extras_take_only_numbers_and_ranges() {
+        assert_eq!(parse_extras("1 3", 3).unwrap(), [0, 2]);
+        assert_eq!(parse_extras("2-3, 2", 3).unwrap(), [1, 2]);
+        assert!(parse_extras("\n", 3).unwrap().is_empty());
+        for bad in ["^2", "4", "0", "3-1", "x", "-1"] {
+            assert!(parse_extras(bad, 3).is_err(), "{bad}");
+        }
+    }
+}
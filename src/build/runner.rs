@@ -0,0 +1,159 @@
+//! How makepkg gets executed for a build, depending on `RootMode` and euid.
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use which::which;
+
+use super::sandbox::Sandbox;
+use super::srcinfo;
+use super::trustroot::{self, TrustRoot};
+use super::usermode::BuildUser;
+use crate::config::{Config, RootMode};
+use crate::is_root;
+
+pub enum Runner {
+    /// Run makepkg as ourselves (the normal non-root case)
+    Direct,
+    /// We are root: drop to the build user for makepkg
+    User(BuildUser),
+    /// We are root: build user inside user/mount/pid namespaces
+    Sandbox(Sandbox),
+    /// We are root and trust the PKGBUILDs: patched makepkg, as root
+    TrustRoot(TrustRoot),
+}
+
+impl Runner {
+    pub fn select(cfg: &Config) -> Result<Self> {
+        if !is_root() {
+            return Ok(Runner::Direct);
+        }
+        let runner = match cfg.root_mode {
+            RootMode::User => Runner::User(BuildUser::lookup(&cfg.build_user)?),
+            RootMode::Sandbox => Runner::Sandbox(Sandbox::new(
+                &cfg.bwrap,
+                BuildUser::lookup(&cfg.build_user)?,
+                cfg.sandbox_net,
+            )?),
+            RootMode::TrustRoot => Runner::TrustRoot(TrustRoot::new(&cfg.makepkg)?),
+            RootMode::Auto => Self::auto(cfg)?,
+        };
+        match &runner {
+            Runner::Direct => {}
+            Runner::User(user) => {
+                if cfg.verbose {
+                    eprintln!(
+                        "==> running as root: building as {} (uid {})",
+                        user.name, user.uid
+                    );
+                }
+            }
+            Runner::Sandbox(sandbox) => {
+                if cfg.verbose {
+                    eprintln!(
+                        "==> running as root: building in sandbox ({})",
+                        sandbox.describe()
+                    );
+                }
+            }
+            Runner::TrustRoot(_) => trustroot::print_banner(),
+        }
+        Ok(runner)
+    }
+
+    /// `RootMode::Auto`: Sandbox, then User, then (only with auto_trust_root) TrustRoot.
+    fn auto(cfg: &Config) -> Result<Self> {
+        let user = BuildUser::lookup(&cfg.build_user);
+        match user {
+            Ok(user) if Sandbox::available(&cfg.bwrap) => Ok(Runner::Sandbox(Sandbox::new(
+                &cfg.bwrap,
+                user,
+                cfg.sandbox_net,
+            )?)),
+            Ok(user) => Ok(Runner::User(user)),
+            Err(_) if cfg.auto_trust_root => Ok(Runner::TrustRoot(TrustRoot::new(&cfg.makepkg)?)),
+            Err(e) => Err(e.context(concat!(
+                "no safe way to build as root ",
+                "(set YAORUST_AUTO_TRUST_ROOT=1 to allow a patched makepkg)"
+            ))),
+        }
+    }
+
+    /// The user-level makepkg.conf the makepkg we run will read: ours, the
+    /// build user's, or none in the sandbox (HOME is the work dir there).
+    pub fn user_makepkg_conf(&self) -> Option<PathBuf> {
+        match self {
+            Runner::Direct | Runner::TrustRoot(_) => srcinfo::user_conf(
+                std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from),
+                std::env::var_os("HOME").as_deref().map(Path::new),
+            ),
+            // started with a scrubbed environment: no XDG_CONFIG_HOME
+            Runner::User(user) => srcinfo::user_conf(None, Some(&user.home)),
+            Runner::Sandbox(_) => None,
+        }
+    }
+
+    /// Whether makepkg may install missing repo deps itself (`--syncdeps`).
+    /// The build user has no sudo rights, so as root we install them up-front.
+    pub fn can_syncdeps(&self) -> bool {
+        matches!(self, Runner::Direct | Runner::TrustRoot(_))
+    }
+
+    /// Hand the work dir to whoever runs makepkg, and return the directory
+    /// makepkg should write packages to.
+    pub fn prepare(&self, work: &Path, pkgdest: &Path) -> Result<PathBuf> {
+        match self {
+            Runner::Direct | Runner::TrustRoot(_) => Ok(pkgdest.to_path_buf()),
+            Runner::User(user) | Runner::Sandbox(Sandbox { user, .. }) => {
+                // PKGDEST is usually root-owned; stage inside the work dir and
+                // move the results over as root afterwards.
+                let staging = work.join("pkgdest");
+                fs::create_dir_all(&staging)?;
+                user.chown_tree(work)?;
+                Ok(staging)
+            }
+        }
+    }
+
+    /// For a sandbox without network: a `makepkg --verifysource` run (with
+    /// network) that downloads sources before the offline build.
+    pub fn fetch_sources(
+        &self,
+        makepkg: &str,
+        build_dir: &Path,
+        pkgdest: &Path,
+    ) -> Result<Option<Command>> {
+        let Runner::Sandbox(sandbox) = self else {
+            return Ok(None);
+        };
+        if sandbox.net() {
+            return Ok(None);
+        }
+        let mut cmd = sandbox.command(&which(makepkg)?, work_dir(build_dir), true);
+        cmd.current_dir(build_dir)
+            .env("PKGDEST", pkgdest)
+            .arg("--verifysource");
+        Ok(Some(cmd))
+    }
+
+    /// A `makepkg` command for `build_dir` writing packages to `pkgdest`.
+    pub fn makepkg(&self, makepkg: &str, build_dir: &Path, pkgdest: &Path) -> Result<Command> {
+        let makepkg = which(makepkg)?;
+        let mut cmd = match self {
+            Runner::Direct => Command::new(makepkg),
+            Runner::TrustRoot(tr) => tr.command(),
+            Runner::User(user) => user.command(&makepkg),
+            Runner::Sandbox(sandbox) => {
+                sandbox.command(&makepkg, work_dir(build_dir), sandbox.net())
+            }
+        };
+        cmd.current_dir(build_dir).env("PKGDEST", pkgdest);
+        Ok(cmd)
+    }
+}
+
+/// The per-build work dir (snapshot extraction root) containing `build_dir`.
+fn work_dir(build_dir: &Path) -> &Path {
+    build_dir.parent().unwrap_or(build_dir)
+}
@@ -0,0 +1,100 @@
+//! `RootMode::Sandbox`: run makepkg inside Linux namespaces.
+//!
+//! makepkg runs as the build user, additionally confined to its own user,
+//! mount and pid namespaces (and optionally a network namespace), with `/usr`
+//! bind-mounted read-only and only the work dir (build dir + staging PKGDEST)
+//! writable. This needs bubblewrap: without it there is no sandbox (a bare
+//! unshare(2) would leave the rest of the host writable), and `Auto` falls
+//! back to the plain build user.
+
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use which::which;
+
+use super::usermode::BuildUser;
+use crate::error::YaoError;
+
+pub struct Sandbox {
+    pub user: BuildUser,
+    /// bubblewrap binary
+    bwrap: PathBuf,
+    /// Keep network access (needed to download sources)
+    net: bool,
+}
+
+impl Sandbox {
+    pub fn new(bwrap: &str, user: BuildUser, net: bool) -> Result<Self> {
+        let Ok(bwrap) = which(bwrap) else {
+            bail!(YaoError::Permission(format!(
+                "the sandbox needs bubblewrap ({bwrap}), which is not installed"
+            )));
+        };
+        Ok(Self { user, bwrap, net })
+    }
+
+    /// Whether bubblewrap is installed and unprivileged user namespaces can
+    /// be created here (bwrap on Arch is not setuid, so it needs them).
+    pub fn available(bwrap: &str) -> bool {
+        if which(bwrap).is_err() {
+            return false;
+        }
+        let sysctl = |p: &str| {
+            std::fs::read_to_string(p)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        // linux-hardened and some distros gate this behind a sysctl
+        if sysctl("/proc/sys/kernel/unprivileged_userns_clone") == Some(0) {
+            return false;
+        }
+        sysctl("/proc/sys/user/max_user_namespaces").is_none_or(|n| n > 0)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "bubblewrap as {} (uid {}){}",
+            self.user.name,
+            self.user.uid,
+            if self.net { "" } else { ", no network" }
+        )
+    }
+
+    pub fn net(&self) -> bool {
+        self.net
+    }
+
+    /// A command running `program` in the sandbox; `work` is the only
+    /// writable tree. `net` overrides the network setting for this call.
+    pub fn command(&self, program: &Path, work: &Path, net: bool) -> Command {
+        // bwrap itself runs as the build user, so the sandbox uid maps to an
+        // unprivileged uid outside as well
+        let mut cmd = self.user.command(&self.bwrap);
+        let uid = self.user.uid.to_string();
+        let gid = self.user.gid.to_string();
+        cmd.args(["--unshare-user", "--uid", &uid, "--gid", &gid])
+            .args(["--unshare-pid", "--unshare-ipc", "--unshare-uts"])
+            .arg("--die-with-parent");
+        if !net {
+            cmd.arg("--unshare-net");
+        }
+        cmd.args(["--ro-bind", "/usr", "/usr"])
+            .args(["--symlink", "usr/bin", "/bin"])
+            .args(["--symlink", "usr/bin", "/sbin"])
+            .args(["--symlink", "usr/lib", "/lib"])
+            .args(["--symlink", "usr/lib", "/lib64"])
+            .args(["--ro-bind", "/etc", "/etc"])
+            .args(["--ro-bind", "/var/lib/pacman", "/var/lib/pacman"])
+            .args(["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"])
+            .arg("--bind")
+            .arg(work)
+            .arg(work)
+            // the real HOME is not visible in the sandbox
+            .arg("--setenv")
+            .arg("HOME")
+            .arg(work)
+            .arg("--")
+            .arg(program);
+        cmd
+    }
+}
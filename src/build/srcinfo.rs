@@ -0,0 +1,521 @@
+//! `.SRCINFO` parser.
+//!
+//! Every AUR snapshot ships a `.SRCINFO`: the PKGBUILD's metadata as plain
+//! `key = value` lines, one `pkgbase` section followed by a `pkgname`
+//! section per (split) package. Reading it gives us versions, package file
+//! names and dependencies without sourcing the PKGBUILD in bash.
+
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An array field: values for every architecture plus per-arch additions
+/// (`depends_x86_64`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchVec {
+    pub all: Vec<String>,
+    pub arch: BTreeMap<String, Vec<String>>,
+}
+
+impl ArchVec {
+    /// Values that apply when building for `carch`.
+    pub fn for_arch<'a>(&'a self, carch: &str) -> impl Iterator<Item = &'a String> {
+        self.all
+            .iter()
+            .chain(self.arch.get(carch).into_iter().flatten())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SrcInfo {
+    pub pkgbase: String,
+    pub pkgver: String,
+    pub pkgrel: String,
+    pub epoch: Option<String>,
+    pub arch: Vec<String>,
+    /// PKGBUILD `options`, overriding makepkg.conf's OPTIONS
+    pub options: Vec<String>,
+    /// Global (pkgbase level) dependencies, as makepkg --syncdeps sees them
+    pub depends: ArchVec,
+    pub makedepends: ArchVec,
+    pub checkdepends: ArchVec,
+    /// One entry per pkgname, with split package overrides applied
+    pub packages: Vec<Package>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Package {
+    pub pkgname: String,
+    pub arch: Vec<String>,
+    /// The package's own runtime dependencies (pkgbase level unless overridden)
+    pub depends: ArchVec,
+    pub provides: ArchVec,
+}
+
+/// Raw `key -> values` of one section, in file order per key.
+type Fields = BTreeMap<String, Vec<String>>;
+
+impl SrcInfo {
+    /// Read `.SRCINFO` from a PKGBUILD directory.
+    pub fn read(dir: &Path) -> Result<Self> {
+        let path = dir.join(".SRCINFO");
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("cannot read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut base: Option<Fields> = None;
+        let mut pkgs: Vec<Fields> = Vec::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                bail!("line {}: expected `key = value`", n + 1);
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            match key {
+                "pkgbase" => {
+                    if base.is_some() {
+                        bail!("line {}: more than one pkgbase", n + 1);
+                    }
+                    base = Some(Fields::from([(key.to_string(), vec![value.to_string()])]));
+                }
+                "pkgname" => {
+                    pkgs.push(Fields::from([(key.to_string(), vec![value.to_string()])]));
+                }
+                _ => {
+                    let section = match pkgs.last_mut() {
+                        Some(pkg) => pkg,
+                        None => base
+                            .as_mut()
+                            .with_context(|| format!("line {}: `{key}` before pkgbase", n + 1))?,
+                    };
+                    let values = section.entry(key.to_string()).or_default();
+                    // `depends =` in a split package clears the inherited value
+                    if !value.is_empty() {
+                        values.push(value.to_string());
+                    }
+                }
+            }
+        }
+
+        let Some(base) = base else {
+            bail!("no pkgbase section");
+        };
+        if pkgs.is_empty() {
+            bail!("no pkgname section");
+        }
+
+        let scalar = |f: &Fields, k: &str| f.get(k).and_then(|v| v.first()).cloned();
+        let info = SrcInfo {
+            pkgbase: scalar(&base, "pkgbase").unwrap_or_default(),
+            pkgver: scalar(&base, "pkgver").context("missing pkgver")?,
+            pkgrel: scalar(&base, "pkgrel").context("missing pkgrel")?,
+            epoch: scalar(&base, "epoch").filter(|e| e != "0"),
+            arch: base.get("arch").cloned().unwrap_or_default(),
+            options: base.get("options").cloned().unwrap_or_default(),
+            depends: arch_vec(&base, "depends"),
+            makedepends: arch_vec(&base, "makedepends"),
+            checkdepends: arch_vec(&base, "checkdepends"),
+            packages: pkgs
+                .iter()
+                .map(|pkg| {
+                    Package {
+                        pkgname: scalar(pkg, "pkgname").unwrap_or_default(),
+                        // a split package may override arch (e.g. `any` for -docs)
+                        arch: pkg
+                            .get("arch")
+                            .or_else(|| base.get("arch"))
+                            .cloned()
+                            .unwrap_or_default(),
+                        depends: inherit(&base, pkg, "depends"),
+                        provides: inherit(&base, pkg, "provides"),
+                    }
+                })
+                .collect(),
+        };
+        Ok(info)
+    }
+
+    /// `[epoch:]pkgver-pkgrel`
+    pub fn version(&self) -> String {
+        match &self.epoch {
+            Some(epoch) => format!("{epoch}:{}-{}", self.pkgver, self.pkgrel),
+            None => format!("{}-{}", self.pkgver, self.pkgrel),
+        }
+    }
+
+    /// Repo/AUR dependencies makepkg needs to build this pkgbase for `carch`,
+    /// including those only split packages declare, without duplicates.
+    pub fn build_depends(&self, carch: &str) -> Vec<&String> {
+        let mut out: Vec<&String> = Vec::new();
+        let all = self
+            .depends
+            .for_arch(carch)
+            .chain(self.packages.iter().flat_map(|p| p.depends.for_arch(carch)))
+            .chain(self.makedepends.for_arch(carch))
+            .chain(self.checkdepends.for_arch(carch));
+        for dep in all {
+            if !out.contains(&dep) {
+                out.push(dep);
+            }
+        }
+        out
+    }
+
+    /// makepkg's check_option: the PKGBUILD's `options` win over OPTIONS
+    /// from makepkg.conf, the last mention of `opt` / `!opt` counts.
+    pub fn option_enabled(&self, conf: &MakepkgConf, opt: &str) -> bool {
+        let lookup = |list: &[String]| {
+            list.iter().rev().find_map(|o| match o.strip_prefix('!') {
+                Some(negated) => (negated == opt).then_some(false),
+                None => (o == opt).then_some(true),
+            })
+        };
+        lookup(&self.options)
+            .or_else(|| lookup(&conf.options))
+            .unwrap_or(false)
+    }
+
+    /// The `{pkgbase}-debug` package makepkg splits off with `debug` and
+    /// `strip` enabled. It is only written if there were symbols to strip.
+    pub fn debug_package(&self, conf: &MakepkgConf) -> Option<Package> {
+        let binary = self.arch.iter().any(|a| a != "any");
+        (binary && self.option_enabled(conf, "debug") && self.option_enabled(conf, "strip")).then(
+            || Package {
+                pkgname: format!("{}-debug", self.pkgbase),
+                arch: vec![conf.carch.clone()],
+                ..Default::default()
+            },
+        )
+    }
+
+    /// The file makepkg writes for `pkg` (what `makepkg --packagelist` prints).
+    pub fn package_file(&self, pkg: &Package, conf: &MakepkgConf, pkgdest: &Path) -> PathBuf {
+        let arch = if pkg.arch.iter().any(|a| a == "any") {
+            "any"
+        } else {
+            &conf.carch
+        };
+        pkgdest.join(format!(
+            "{}-{}-{arch}{}",
+            pkg.pkgname,
+            self.version(),
+            conf.pkgext
+        ))
+    }
+}
+
+/// Collect `field` and its `field_<arch>` variants from a section.
+fn arch_vec(fields: &Fields, field: &str) -> ArchVec {
+    let mut out = ArchVec::default();
+    for (key, values) in fields {
+        let arch = if key == field {
+            None
+        } else if let Some(arch) = key
+            .strip_prefix(field)
+            .and_then(|rest| rest.strip_prefix('_'))
+        {
+            Some(arch)
+        } else {
+            continue;
+        };
+        match arch {
+            None => out.all = values.clone(),
+            Some(arch) => {
+                out.arch.insert(arch.to_string(), values.clone());
+            }
+        }
+    }
+    out
+}
+
+/// `field` of a split package: the pkgbase value, with every `field` or
+/// `field_<arch>` the package sets itself replacing the inherited one.
+fn inherit(base: &Fields, pkg: &Fields, field: &str) -> ArchVec {
+    let mut out = arch_vec(base, field);
+    let own = arch_vec(pkg, field);
+    if pkg.contains_key(field) {
+        out.all = own.all;
+    }
+    out.arch.extend(own.arch);
+    out
+}
+
+/// The bits of makepkg.conf needed to predict package file names.
+#[derive(Debug, Clone)]
+pub struct MakepkgConf {
+    pub carch: String,
+    pub pkgext: String,
+    /// OPTIONS=(...), e.g. `strip`, `!debug`
+    pub options: Vec<String>,
+}
+
+impl MakepkgConf {
+    /// Read the config files makepkg loads for `--config /etc/makepkg.conf`:
+    /// the file itself, `makepkg.conf.d/*.conf`, then `user_conf` (the
+    /// user-level config of whoever runs makepkg, see [`user_conf`]).
+    /// `PKGEXT` from the environment wins, as in makepkg.
+    pub fn load(user_conf: Option<PathBuf>) -> Self {
+        let mut conf = MakepkgConf {
+            carch: std::env::consts::ARCH.to_string(),
+            pkgext: ".pkg.tar.zst".to_string(),
+            options: Vec::new(),
+        };
+
+        let mut files = vec![PathBuf::from("/etc/makepkg.conf")];
+        if let Ok(dir) = fs::read_dir("/etc/makepkg.conf.d") {
+            let mut extra: Vec<PathBuf> = dir
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|e| e == "conf"))
+                .collect();
+            extra.sort();
+            files.extend(extra);
+        }
+        files.extend(user_conf);
+
+        for file in files {
+            if let Ok(text) = fs::read_to_string(&file) {
+                conf.apply(&text);
+            }
+        }
+        if let Ok(ext) = std::env::var("PKGEXT")
+            && !ext.is_empty()
+        {
+            conf.pkgext = ext;
+        }
+        conf
+    }
+
+    /// Pick up plain `CARCH=`/`PKGEXT=` assignments and the OPTIONS array
+    /// (no shell evaluation).
+    fn apply(&mut self, text: &str) {
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let Some((key, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            if key == "OPTIONS"
+                && let Some(rest) = value.trim_start().strip_prefix('(')
+            {
+                let mut array = strip_comment(rest).to_string();
+                while !array.contains(')')
+                    && let Some(next) = lines.next()
+                {
+                    array.push(' ');
+                    array.push_str(strip_comment(next));
+                }
+                let array = array.split(')').next().unwrap_or("");
+                self.options = array
+                    .split_whitespace()
+                    .map(|w| w.trim_matches(|c| c == '\'' || c == '"').to_string())
+                    .collect();
+                continue;
+            }
+            let value = shell_word(value);
+            if value.is_empty() {
+                continue;
+            }
+            match key {
+                "CARCH" => self.carch = value,
+                "PKGEXT" => self.pkgext = value,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The user-level makepkg.conf makepkg reads: `$XDG_CONFIG_HOME/pacman/makepkg.conf`
+/// if it exists, else `~/.makepkg.conf`.
+pub fn user_conf(xdg_config_home: Option<PathBuf>, home: Option<&Path>) -> Option<PathBuf> {
+    xdg_config_home
+        .or_else(|| home.map(|h| h.join(".config")))
+        .map(|x| x.join("pacman/makepkg.conf"))
+        .filter(|p| p.is_file())
+        .or_else(|| home.map(|h| h.join(".makepkg.conf")))
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or("")
+}
+
+/// The value of a simple shell assignment: quoted, or up to a comment/space.
+fn shell_word(value: &str) -> String {
+    let value = value.trim_start();
+    for quote in ['\'', '"'] {
+        if let Some(rest) = value.strip_prefix(quote) {
+            return rest.split(quote).next().unwrap_or("").to_string();
+        }
+    }
+    value
+        .split(|c: char| c.is_whitespace() || c == '#')
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPLIT: &str = "\
+pkgbase = foo
+\tpkgdesc = Foo things
+\tpkgver = 1.2
+\tpkgrel = 3
+\tepoch = 1
+\tarch = x86_64
+\tarch = aarch64
+\tmakedepends = cmake
+\tdepends = glibc
+\tdepends_x86_64 = lib32-glibc
+\tprovides = libfoo.so
+\tsource = foo.tar.gz
+
+pkgname = foo
+\tdepends = glibc
+\tdepends = zlib
+
+pkgname = foo-docs
+\tpkgdesc = Foo documentation
+\tarch = any
+\tdepends =
+\tprovides =
+";
+
+    #[test]
+    fn parses_split_package_overrides() {
+        let info = SrcInfo::parse(SPLIT).unwrap();
+        assert_eq!(info.pkgbase, "foo");
+        assert_eq!(info.version(), "1:1.2-3");
+        assert_eq!(info.makedepends.all, ["cmake"]);
+
+        // a split package inherits what it does not override
+        assert_eq!(info.packages[0].pkgname, "foo");
+        assert_eq!(info.packages[0].arch, ["x86_64", "aarch64"]);
+        assert_eq!(info.packages[1].pkgname, "foo-docs");
+        assert_eq!(info.packages[1].arch, ["any"]);
+
+        assert_eq!(info.depends.all, ["glibc"]);
+        assert_eq!(
+            info.depends.for_arch("x86_64").collect::<Vec<_>>(),
+            ["glibc", "lib32-glibc"]
+        );
+        assert_eq!(info.depends.for_arch("aarch64").count(), 1);
+
+        // `depends =` replaces the inherited list; depends_x86_64 is its own field
+        let (foo, docs) = (&info.packages[0], &info.packages[1]);
+        assert_eq!(foo.depends.all, ["glibc", "zlib"]);
+        assert_eq!(
+            foo.depends.for_arch("x86_64").collect::<Vec<_>>(),
+            ["glibc", "zlib", "lib32-glibc"]
+        );
+        assert_eq!(foo.provides.all, ["libfoo.so"]);
+        assert!(docs.depends.all.is_empty());
+        assert_eq!(
+            docs.depends.for_arch("x86_64").collect::<Vec<_>>(),
+            ["lib32-glibc"]
+        );
+        assert!(docs.provides.all.is_empty());
+
+        assert_eq!(
+            info.build_depends("x86_64"),
+            ["glibc", "lib32-glibc", "zlib", "cmake"]
+        );
+    }
+
+    #[test]
+    fn package_file_names() {
+        let info = SrcInfo::parse(SPLIT).unwrap();
+        let conf = MakepkgConf {
+            carch: "x86_64".into(),
+            pkgext: ".pkg.tar.zst".into(),
+            options: Vec::new(),
+        };
+        let dest = Path::new("/var/cache/makepkg");
+        assert_eq!(
+            info.package_file(&info.packages[0], &conf, dest),
+            dest.join("foo-1:1.2-3-x86_64.pkg.tar.zst")
+        );
+        assert_eq!(
+            info.package_file(&info.packages[1], &conf, dest),
+            dest.join("foo-docs-1:1.2-3-any.pkg.tar.zst")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert!(SrcInfo::parse("").is_err());
+        assert!(SrcInfo::parse("pkgver = 1\n").is_err());
+        assert!(SrcInfo::parse("pkgbase = a\npkgver = 1\npkgrel = 1\n").is_err());
+        assert!(SrcInfo::parse("pkgbase = a\ngarbage\n").is_err());
+    }
+
+    #[test]
+    fn makepkg_conf_assignments() {
+        let mut conf = MakepkgConf {
+            carch: "x".into(),
+            pkgext: "y".into(),
+            options: Vec::new(),
+        };
+        conf.apply("CARCH=\"aarch64\"\n#PKGEXT='.pkg.tar.xz'\nPKGEXT='.pkg.tar.gz' # comment\n");
+        assert_eq!(conf.carch, "aarch64");
+        assert_eq!(conf.pkgext, ".pkg.tar.gz");
+        assert_eq!(shell_word("foo # bar"), "foo");
+
+        conf.apply("OPTIONS=(strip docs !libtool # trailing\n  !debug lto)\n");
+        assert_eq!(conf.options, ["strip", "docs", "!libtool", "!debug", "lto"]);
+    }
+
+    #[test]
+    fn user_conf_of_the_given_home() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let home = tmp.path();
+        assert_eq!(
+            user_conf(None, Some(home)),
+            Some(home.join(".makepkg.conf"))
+        );
+
+        let xdg = home.join(".config/pacman/makepkg.conf");
+        fs::create_dir_all(xdg.parent().unwrap()).unwrap();
+        fs::write(&xdg, "PKGEXT='.pkg.tar'\n").unwrap();
+        assert_eq!(user_conf(None, Some(home)), Some(xdg));
+        // an XDG_CONFIG_HOME without the file falls back to ~/.makepkg.conf
+        assert_eq!(
+            user_conf(Some(home.join("elsewhere")), Some(home)),
+            Some(home.join(".makepkg.conf"))
+        );
+        assert_eq!(user_conf(None, None), None);
+    }
+
+    #[test]
+    fn debug_package_follows_options() {
+        let mut info = SrcInfo::parse(SPLIT).unwrap();
+        let mut conf = MakepkgConf {
+            carch: "x86_64".into(),
+            pkgext: ".pkg.tar.zst".into(),
+            options: vec!["strip".into(), "debug".into()],
+        };
+        let debug = info.debug_package(&conf).unwrap();
+        assert_eq!(debug.pkgname, "foo-debug");
+        assert_eq!(
+            info.package_file(&debug, &conf, Path::new("/d")),
+            Path::new("/d/foo-debug-1:1.2-3-x86_64.pkg.tar.zst")
+        );
+
+        info.options = vec!["!debug".into()];
+        assert!(info.debug_package(&conf).is_none());
+
+        info.options = vec!["debug".into()];
+        conf.options = vec!["!strip".into()];
+        assert!(info.debug_package(&conf).is_none());
+        conf.options = vec!["strip".into()];
+        assert!(info.debug_package(&conf).is_some());
+    }
+}
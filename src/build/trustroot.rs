@@ -0,0 +1,152 @@
+//! `RootMode::TrustRoot`: build as root with a private, patched copy of makepkg.
+//!
+//! makepkg refuses to run with EUID 0. When the user explicitly trusts the
+//! PKGBUILDs, we copy makepkg, neutralize exactly that refusal, and run the
+//! copy. The refusal block must match the shape we know; if upstream makepkg
+//! changes it, we refuse to patch instead of guessing.
+
+use anyhow::{Result, bail};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use tempfile::TempDir;
+use which::which;
+
+/// The guard line we patch, and what we replace it with.
+const GUARD: &str = "if (( EUID == 0 )); then";
+const PATCHED_GUARD: &str = "if (( EUID == 0 )) && false; then # patched by yaorust (trust-root)";
+
+/// Lines that must follow the guard (before its `fi`) for the patch to apply.
+const FINGERPRINT: &[&str] = &["Running %s as root is not allowed", "exit $E_ROOT"];
+
+/// A patched makepkg living in a private temp dir for the lifetime of the run.
+/// It is run through bash rather than exec'd, so a `noexec` /tmp works too.
+pub struct TrustRoot {
+    _dir: TempDir,
+    makepkg: PathBuf,
+}
+
+impl TrustRoot {
+    /// Patch a copy of `makepkg` (name or path).
+    pub fn new(makepkg: &str) -> Result<Self> {
+        let orig = which(makepkg)?;
+        let script = fs::read_to_string(&orig)?;
+        let patched = patch_makepkg(&script)
+            .map_err(|e| e.context(format!("cannot patch {}", orig.display())))?;
+
+        let dir = TempDir::new()?;
+        let makepkg = dir.path().join("makepkg");
+        fs::write(&makepkg, patched)?;
+        fs::set_permissions(&makepkg, fs::Permissions::from_mode(0o600))?;
+        Ok(Self { _dir: dir, makepkg })
+    }
+
+    /// `bash <patched makepkg>`; makepkg re-runs itself through bash as well.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new("bash");
+        cmd.arg(&self.makepkg);
+        cmd
+    }
+}
+
+/// Remove makepkg's EUID==0 refusal. Fails if the refusal block does not look
+/// exactly like the one we know (exactly one guard, fingerprint lines present).
+fn patch_makepkg(script: &str) -> Result<String> {
+    let lines: Vec<&str> = script.lines().collect();
+    let guards: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.trim() == GUARD)
+        .map(|(i, _)| i)
+        .collect();
+    let [at] = guards[..] else {
+        bail!(
+            "expected exactly one `{GUARD}` root check in makepkg, found {}; upstream makepkg changed",
+            guards.len()
+        );
+    };
+
+    let block: Vec<&str> = lines[at + 1..]
+        .iter()
+        .take_while(|l| l.trim() != "fi")
+        .copied()
+        .collect();
+    for needle in FINGERPRINT {
+        if !block.iter().any(|l| l.contains(needle)) {
+            bail!("makepkg root check does not contain `{needle}`; upstream makepkg changed");
+        }
+    }
+
+    let indent = &lines[at][..lines[at].len() - lines[at].trim_start().len()];
+    let mut out = String::with_capacity(script.len() + PATCHED_GUARD.len());
+    for (i, line) in lines.iter().enumerate() {
+        if i == at {
+            out.push_str(indent);
+            out.push_str(PATCHED_GUARD);
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+pub fn print_banner() {
+    let bar = "!".repeat(72);
+    eprintln!("{bar}");
+    eprintln!("!! WARNING: trust-root mode -- running a PATCHED makepkg AS ROOT.");
+    eprintln!("!! Every PKGBUILD function runs with full root privileges on this");
+    eprintln!("!! system. Only continue if you trust these PKGBUILDs completely.");
+    eprintln!("{bar}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNIPPET: &str = r#"
+if (( ! INFAKEROOT )); then
+	if (( EUID == 0 )); then
+		error "$(gettext "Running %s as root is not allowed as it can cause permanent,\n\
+catastrophic damage to your system.")" "makepkg"
+		exit $E_ROOT
+	fi
+else
+	if [[ -z $FAKEROOTKEY ]]; then
+		error "$(gettext "Do not use the %s option. This option is only for internal use by %s.")" "'-F'" "makepkg"
+		exit $E_INVALID_OPTION
+	fi
+fi
+"#;
+
+    #[test]
+    fn patches_known_root_check() {
+        let out = patch_makepkg(SNIPPET).unwrap();
+        assert!(out.contains(&format!("\t{PATCHED_GUARD}\n")));
+        assert!(!out.lines().any(|l| l.trim() == GUARD));
+        assert_eq!(out.lines().count(), SNIPPET.lines().count());
+    }
+
+    #[test]
+    fn runs_patched_copy_through_bash() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let orig = tmp.path().join("makepkg");
+        fs::write(&orig, format!("#!/bin/bash\n{SNIPPET}echo built\n")).unwrap();
+        fs::set_permissions(&orig, fs::Permissions::from_mode(0o755)).unwrap();
+        let tr = TrustRoot::new(orig.to_str().unwrap()).unwrap();
+
+        let out = tr.command().output().unwrap();
+        assert!(out.status.success());
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "built\n");
+    }
+
+    #[test]
+    fn refuses_unknown_makepkg() {
+        assert!(patch_makepkg("echo no guard here").is_err());
+        let changed = SNIPPET.replace("exit $E_ROOT", "exit 1");
+        assert!(patch_makepkg(&changed).is_err());
+        let twice = format!("{SNIPPET}{SNIPPET}");
+        assert!(patch_makepkg(&twice).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! `RootMode::User`: run makepkg as an unprivileged build user when we are root.
+//!
+//! makepkg refuses to run as root, so the build dir is handed over to
+//! `build_user` and makepkg is exec'd in a child that dropped to that user
+//! (setgroups + setgid + setuid) with a scrubbed environment. The group list
+//! is resolved up-front: NSS lookups are not safe between fork and exec.
+
+use anyhow::{Context, Result, bail};
+use std::ffi::{CStr, CString, OsStr};
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variables passed through to the build user; everything else is
+/// dropped. `PKGEXT`/`SRCEXT`/`PKGDEST` keep the package names we predicted
+/// from our own environment (see `MakepkgConf::load`) right; the proxy
+/// variables are needed for source downloads, `GNUPGHOME` for verifying them.
+const ENV_PASSTHROUGH: &[&str] = &[
+    "LANG",
+    "LC_ALL",
+    "LC_MESSAGES",
+    "TERM",
+    "MAKEFLAGS",
+    "PKGEXT",
+    "SRCEXT",
+    "PKGDEST",
+    "SRCDEST",
+    "PACKAGER",
+    "GNUPGHOME",
+    "http_proxy",
+    "https_proxy",
+    "ftp_proxy",
+    "no_proxy",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "FTP_PROXY",
+    "NO_PROXY",
+];
+
+#[derive(Debug, Clone)]
+pub struct BuildUser {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: PathBuf,
+    /// Supplementary groups (`getgrouplist`, including `gid`)
+    groups: Vec<libc::gid_t>,
+}
+
+impl BuildUser {
+    /// Look the user up in the passwd database.
+    pub fn lookup(name: &str) -> Result<Self> {
+        let cname = CString::new(name).context("invalid build user name")?;
+        // getpwnam returns a pointer into static storage; copy out what we need
+        let pw = unsafe { libc::getpwnam(cname.as_ptr()) };
+        if pw.is_null() {
+            bail!("build user '{name}' does not exist (create it or set YAORUST_BUILD_USER)");
+        }
+        let (uid, gid, home) = unsafe {
+            let home = CStr::from_ptr((*pw).pw_dir).to_bytes();
+            (
+                (*pw).pw_uid,
+                (*pw).pw_gid,
+                PathBuf::from(OsStr::from_bytes(home)),
+            )
+        };
+        if uid == 0 {
+            bail!("build user '{name}' is root; pick an unprivileged user");
+        }
+        Ok(Self {
+            name: name.to_string(),
+            uid,
+            gid,
+            home,
+            groups: group_list(&cname, gid)?,
+        })
+    }
+
+    /// Recursively chown `path` to the build user (symlinks are not followed).
+    pub fn chown_tree(&self, path: &Path) -> Result<()> {
+        std::os::unix::fs::lchown(path, Some(self.uid), Some(self.gid))
+            .with_context(|| format!("chown {} to {}", path.display(), self.name))?;
+        let meta = fs::symlink_metadata(path)?;
+        if meta.is_dir() {
+            for entry in fs::read_dir(path)? {
+                self.chown_tree(&entry?.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A command that execs `program` as the build user with a sanitized
+    /// environment (PATH, HOME, USER, LOGNAME + a few locale/terminal/makepkg vars).
+    pub fn command(&self, program: &Path) -> Command {
+        let mut cmd = Command::new(program);
+        cmd.env_clear()
+            .env("PATH", "/usr/local/sbin:/usr/local/bin:/usr/bin")
+            .env("HOME", &self.home)
+            .env("USER", &self.name)
+            .env("LOGNAME", &self.name);
+        for var in ENV_PASSTHROUGH {
+            if let Some(v) = std::env::var_os(var) {
+                cmd.env(var, v);
+            }
+        }
+
+        let (uid, gid) = (self.uid, self.gid);
+        let groups = self.groups.clone();
+        // Runs in the forked child before exec: only async-signal-safe calls.
+        // Order matters: groups and gid must be set while we are still root.
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::setgroups(groups.len() as _, groups.as_ptr()) != 0
+                    || libc::setgid(gid) != 0
+                    || libc::setuid(uid) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        cmd
+    }
+}
+
+/// The groups `name` belongs to, like initgroups(3) would set them.
+fn group_list(name: &CStr, gid: libc::gid_t) -> Result<Vec<libc::gid_t>> {
+    let mut groups: Vec<libc::gid_t> = vec![0; 32];
+    loop {
+        let mut n = groups.len() as libc::c_int;
+        let found = unsafe { libc::getgrouplist(name.as_ptr(), gid, groups.as_mut_ptr(), &mut n) };
+        if found >= 0 {
+            groups.truncate(n as usize);
+            return Ok(groups);
+        }
+        // too small: n now holds the number needed
+        let want = (n as usize).max(groups.len() * 2);
+        if want > 65536 {
+            bail!("cannot list the groups of {}", name.to_string_lossy());
+        }
+        groups.resize(want, 0);
+    }
+}
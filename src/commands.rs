@@ -0,0 +1,407 @@
+//! The `yao` operations (`-S`, `-Syu`, `-Si`, `-Ss`, `-G` and the search
+//! menu), each taking the merged [`Config`].
+
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+use which::which;
+
+use crate::aur::{AurClient, AurSource};
+use crate::build::{Builder, MakepkgBuilder};
+use crate::config::Config;
+use crate::error::YaoError;
+use crate::fetch::FetchBackend;
+use crate::pacman::db::PacmanDb;
+use crate::pacman::vercmp;
+use crate::pacman::{self, PackageDb};
+use crate::providers::Providers;
+use crate::targets::{self, PkgKind};
+use crate::ui::info::print_aur_info;
+use crate::ui::search::{print_hits, prompt_selection, search_all};
+use crate::ui::{prompt_yes_no, run_command_printing};
+use crate::{conflicts, deps, is_root};
+
+pub fn getpkgbuild(cfg: &Config, pkgs: Vec<String>) -> Result<()> {
+    if pkgs.is_empty() {
+        bail!(YaoError::Config("no packages specified for -G".into()));
+    }
+
+    let aur = AurClient::new(cfg)?;
+
+    aur.prefetch(&pkgs)?;
+    let mut done: Vec<String> = Vec::new();
+    for p in pkgs {
+        if !aur.exists(&p)? {
+            bail!(YaoError::NotFound(format!("{p} not found in AUR")));
+        }
+        // split packages live in their pkgbase's repo
+        let base = aur.pkgbase(&p)?;
+        if done.contains(&base) {
+            continue;
+        }
+        let tmp = TempDir::new()?;
+        let src = aur.fetch(cfg, &p, tmp.path())?.dir;
+        let dst = Path::new(&base);
+        if dst.exists() {
+            fs::remove_dir_all(dst)?;
+        }
+        fs::rename(&src, dst)?;
+        eprintln!("==> PKGBUILD for {p} saved to ./{base}");
+        done.push(base);
+    }
+    Ok(())
+}
+
+pub fn search(cfg: &Config, terms: Vec<String>) -> Result<()> {
+    if terms.is_empty() {
+        bail!(YaoError::Config("no search terms specified for -Ss".into()));
+    }
+    let aur = AurClient::new(cfg)?;
+    let hits = search_all(cfg, &aur, &terms)?;
+    print_hits(&hits, false)
+}
+
+/// `-Si`: repo packages go straight to `pacman -Si`, the rest is looked up
+/// in the AUR and printed in the same layout.
+pub fn info(cfg: &Config, pkgs: Vec<String>) -> Result<()> {
+    if pkgs.is_empty() {
+        bail!(YaoError::Config("no packages specified for -Si".into()));
+    }
+
+    let db = PacmanDb::open(cfg)?;
+    let mut aur_names = Vec::new();
+    for p in &pkgs {
+        if db.sync(p).is_some() {
+            let status = Command::new(&cfg.pacman)
+                .arg("-Si")
+                .arg("--")
+                .arg(p)
+                .status()?;
+            if !status.success() {
+                bail!("pacman -Si {p} failed with status {status}");
+            }
+        } else {
+            aur_names.push(p.clone());
+        }
+    }
+    if aur_names.is_empty() {
+        return Ok(());
+    }
+
+    let aur = AurClient::new(cfg)?;
+    let found = aur.multiinfo(&aur_names)?;
+    let mut missing = Vec::new();
+    for name in &aur_names {
+        match found.iter().find(|p| &p.name == name) {
+            Some(pkg) => print_aur_info(pkg)?,
+            None => {
+                eprintln!("error: package '{name}' was not found");
+                missing.push(name.as_str());
+            }
+        }
+    }
+    if !missing.is_empty() {
+        bail!(YaoError::NotFound(format!(
+            "not found in repos or AUR: {}",
+            missing.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// `yao <term>`: list matches with numbers, then install the picked ones.
+pub fn search_install(cfg: &Config, terms: Vec<String>, force: bool) -> Result<()> {
+    let aur = AurClient::new(cfg)?;
+    let hits = search_all(cfg, &aur, &terms)?;
+    if hits.is_empty() {
+        eprintln!(":: No packages found for '{}'.", terms.join(" "));
+        return Ok(());
+    }
+
+    print_hits(&hits, true)?;
+    let picked = prompt_selection(cfg, hits.len())?;
+    if picked.is_empty() {
+        eprintln!(":: Nothing selected.");
+        return Ok(());
+    }
+
+    let pkgs = picked.into_iter().map(|i| hits[i].name.clone()).collect();
+    sync(cfg, pkgs, force)
+}
+
+/// `-S`: repo targets go to `pacman -S`, AUR targets are resolved, built
+/// and installed.
+pub fn sync(cfg: &Config, pkgs: Vec<String>, force: bool) -> Result<()> {
+    if pkgs.is_empty() {
+        bail!(YaoError::Config("no packages specified for -S".into()));
+    }
+
+    let aur = AurClient::new(cfg)?;
+    let db = PacmanDb::open(cfg)?;
+    sync_with(cfg, &aur, &db, &MakepkgBuilder, pkgs, force)
+}
+
+/// [`sync`] with the package database, AUR source and builder supplied by
+/// the caller.
+pub fn sync_with(
+    cfg: &Config,
+    aur: &dyn AurSource,
+    db: &dyn PackageDb,
+    builder: &dyn Builder,
+    pkgs: Vec<String>,
+    force: bool,
+) -> Result<()> {
+    let providers = Providers::new(cfg, aur, db);
+
+    let mut repo_pkgs: Vec<String> = Vec::new();
+    let mut aur_pkgs: Vec<String> = Vec::new();
+
+    // Also record up-to-date status so we can print a warning like pacman
+    struct PlanItem {
+        name: String,
+        kind: PkgKind,
+        up_to_date: bool,
+    }
+
+    let mut plan: Vec<PlanItem> = Vec::new();
+
+    for p in &pkgs {
+        for t in targets::resolve(cfg, db, aur, &providers, p)? {
+            // like pacman, a newer installed version counts as up to date
+            let local = db
+                .local(&t.pkgname)
+                .filter(|l| vercmp::vercmp(&l.version, &t.version).is_ge());
+            let up_to_date = local.is_some();
+            if let (Some(l), true) = (local, cfg.needed) {
+                eprintln!(
+                    "warning: {}-{} is up to date -- skipping",
+                    t.pkgname, l.version
+                );
+                continue;
+            }
+
+            match t.kind {
+                PkgKind::Aur => aur_pkgs.push(t.pkgname.clone()),
+                _ => repo_pkgs.push(t.name.clone()),
+            }
+
+            plan.push(PlanItem {
+                name: t.name,
+                kind: t.kind,
+                up_to_date,
+            });
+        }
+    }
+
+    if repo_pkgs.is_empty() && aur_pkgs.is_empty() {
+        if cfg.needed {
+            eprintln!(" there is nothing to do");
+            return Ok(());
+        }
+        bail!("no packages found in repos or AUR");
+    }
+
+    // PURE REPO: delegate fully to pacman -S
+    if aur_pkgs.is_empty() {
+        eprintln!("==> [repo] delegating to pacman -S");
+        return pacman::install_repo(cfg, &repo_pkgs);
+    }
+
+    // Resolve AUR dependencies (depends/makedepends/checkdepends) up-front,
+    // so a missing or cyclic dep fails before anything is built.
+    let mut build_plan = deps::resolve_aur_deps(cfg, aur, db, &aur_pkgs)?;
+    build_plan.repo_targets = repo_pkgs.clone();
+    // Conflicts are asked about now, not after a long build
+    build_plan.removals = conflicts::check(cfg, aur, db, &build_plan)?;
+
+    // AUR present (maybe mixed with repo): show a simple plan, including
+    // "warning: foo is up to date -- reinstalling" without --needed.
+    eprintln!(":: Packages to process:");
+    for item in &plan {
+        let source = match item.kind {
+            PkgKind::Aur => "AUR",
+            _ => "repo",
+        };
+        eprintln!("   {} ({})", item.name, source);
+        if item.up_to_date {
+            eprintln!("      warning: {} is up to date -- reinstalling", item.name);
+        }
+    }
+    print_plan_deps(&build_plan);
+
+    if !prompt_yes_no(cfg, ":: Proceed with installation? [Y/n] ")? {
+        eprintln!(":: Aborted by user.");
+        bail!(YaoError::Aborted);
+    }
+
+    // 1) Handle repo pkgs first via pacman -S (full pacman output + prompt)
+    if !repo_pkgs.is_empty() {
+        pacman::install_repo(cfg, &repo_pkgs)?;
+    }
+
+    // 2) Then handle AUR packages one by one, dependencies first.
+    build_aur_plan(cfg, aur, db, builder, build_plan, force)
+}
+
+pub fn upgrade(cfg: &Config, refresh: bool, aur_only: bool, force: bool) -> Result<()> {
+    // 1) Repo upgrade first, so AUR builds link against current libraries
+    if !aur_only {
+        pacman::sysupgrade(cfg, refresh)?;
+    }
+
+    // 2) Compare foreign packages against the AUR
+    // read after the repo upgrade, which changed the local DB
+    let aur = AurClient::new(cfg)?;
+    let db = PacmanDb::open(cfg)?;
+    let foreign = db.foreign();
+    if foreign.is_empty() {
+        eprintln!(":: No foreign packages installed.");
+        return Ok(());
+    }
+
+    let names: Vec<String> = foreign.iter().map(|p| p.name.clone()).collect();
+    let remote = aur.multiinfo(&names)?;
+
+    struct Upgrade {
+        name: String,
+        local: String,
+        remote: String,
+    }
+
+    let mut upgrades: Vec<Upgrade> = Vec::new();
+    for local in &foreign {
+        let (name, local) = (&local.name, &local.version);
+        let Some(pkg) = remote.iter().find(|p| &p.name == name) else {
+            if cfg.verbose {
+                eprintln!("==> {name} is not in the AUR, skipping");
+            }
+            continue;
+        };
+        if vercmp::vercmp(local, &pkg.version).is_lt() {
+            upgrades.push(Upgrade {
+                name: name.clone(),
+                local: local.clone(),
+                remote: pkg.version.clone(),
+            });
+        }
+    }
+
+    if upgrades.is_empty() {
+        eprintln!(":: AUR packages are up to date.");
+        return Ok(());
+    }
+
+    let targets: Vec<String> = upgrades.iter().map(|u| u.name.clone()).collect();
+    let mut build_plan = deps::resolve_aur_deps(cfg, &aur, &db, &targets)?;
+    build_plan.removals = conflicts::check(cfg, &aur, &db, &build_plan)?;
+
+    eprintln!(":: Packages to process:");
+    for u in &upgrades {
+        eprintln!("   {} {} -> {} (AUR)", u.name, u.local, u.remote);
+    }
+    print_plan_deps(&build_plan);
+
+    if !prompt_yes_no(cfg, ":: Proceed with upgrade? [Y/n] ")? {
+        eprintln!(":: Aborted by user.");
+        bail!(YaoError::Aborted);
+    }
+
+    build_aur_plan(cfg, &aur, &db, &MakepkgBuilder, build_plan, force)
+}
+
+/// The dependency lines of the plan shown before the proceed prompt.
+fn print_plan_deps(plan: &deps::Plan) {
+    for step in &plan.repo_deps {
+        eprintln!(
+            "   {} (repo dependency of {})",
+            step.name,
+            step.required_by.as_deref().unwrap_or("?")
+        );
+    }
+    for step in plan.steps.iter().filter(|s| s.required_by.is_some()) {
+        eprintln!(
+            "   {} (AUR dependency of {})",
+            step.name,
+            step.required_by.as_deref().unwrap_or("?")
+        );
+    }
+    for r in &plan.removals {
+        let why = if r.replaces {
+            "replaced by"
+        } else {
+            "conflicts with"
+        };
+        eprintln!("   {} (to be removed, {why} {})", r.name, r.by);
+    }
+}
+
+/// Build and install a resolved AUR plan in order.
+/// Force only applies to what the user asked for, not pulled-in deps.
+fn build_aur_plan(
+    cfg: &Config,
+    aur: &dyn AurSource,
+    db: &dyn PackageDb,
+    builder: &dyn Builder,
+    plan: deps::Plan,
+    force: bool,
+) -> Result<()> {
+    // Providers picked for virtual deps, so makepkg finds them satisfied
+    // (`--needed` covers one the repo targets pulled in meanwhile)
+    let repo_deps: Vec<&str> = plan
+        .repo_deps
+        .iter()
+        .map(|s| s.name.as_str())
+        .filter(|n| !db.is_installed(n))
+        .collect();
+    if !repo_deps.is_empty() {
+        let mut cmd = pacman::command(cfg);
+        cmd.arg("-S")
+            .arg("--asdeps")
+            .arg("--needed")
+            .args(&repo_deps);
+        if !is_root() {
+            cmd = pacman::with_sudo(cfg, cmd);
+        }
+        eprintln!("==> Installing chosen providers: {}", repo_deps.join(" "));
+        run_command_printing(&mut cmd, cfg.verbose).context(YaoError::Install(
+            "cannot install the chosen providers".into(),
+        ))?;
+    }
+
+    for build in deps::group_by_pkgbase(aur, plan.steps)? {
+        let names: Vec<&str> = build.members.iter().map(|m| m.name.as_str()).collect();
+        let as_dep = build.members.iter().all(|m| m.as_dep);
+        if names != [build.pkgbase.as_str()] {
+            eprintln!(
+                "==> [aur] building {} ({}){}",
+                build.pkgbase,
+                names.join(" "),
+                if as_dep { " (dependency)" } else { "" }
+            );
+        } else if as_dep {
+            eprintln!("==> [aur] building {} (dependency)", build.pkgbase);
+        } else {
+            eprintln!("==> [aur] building {}", build.pkgbase);
+        }
+        builder.build(cfg, aur, db, &build, &plan.removals, force && !as_dep)?;
+    }
+    Ok(())
+}
+
+pub fn ensure_tools(cfg: &Config) -> Result<()> {
+    let git = (cfg.fetch_backend == FetchBackend::Git).then_some(cfg.git.as_str());
+    for bin in [&cfg.bsdtar, &cfg.makepkg, &cfg.pacman]
+        .map(String::as_str)
+        .into_iter()
+        .chain(git)
+    {
+        let p = which(bin)
+            .with_context(|| YaoError::Config(format!("{bin} not found (check the tool paths)")))?;
+        if cfg.verbose {
+            eprintln!("==> using {bin} at {}", p.display());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,711 @@
+//! Layered configuration.
+//!
+//! Settings are read from, lowest to highest precedence: built-in defaults,
+//! `/etc/yaorust.conf`, `~/.config/yaorust/config.toml`, `YAORUST_*`
+//! environment variables and command-line flags. Both files use the same
+//! small TOML subset:
+//!
+//! ```toml
+//! root_mode = "sandbox"
+//! build_user = "builder"
+//!
+//! [providers]
+//! java-runtime = "jre-openjdk"
+//!
+//! [package.chromium-widevine]
+//! root_mode = "trust-root"
+//! ```
+//!
+//! Every value remembers where it came from, for `yao --show-config`.
+
+use anyhow::{Context, Result, bail};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fetch::FetchBackend;
+use crate::pacman::db as pacdb;
+use crate::providers;
+
+const SYSTEM_CONFIG: &str = "/etc/yaorust.conf";
+
+/// Root-mode behavior: how makepkg is run when we are root
+#[derive(Clone, Copy, Debug)]
+pub enum RootMode {
+    Auto,
+    Sandbox,
+    User,
+    TrustRoot,
+}
+
+impl RootMode {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "auto" => Some(RootMode::Auto),
+            "sandbox" => Some(RootMode::Sandbox),
+            "user" => Some(RootMode::User),
+            "trust-root" => Some(RootMode::TrustRoot),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RootMode::Auto => "auto",
+            RootMode::Sandbox => "sandbox",
+            RootMode::User => "user",
+            RootMode::TrustRoot => "trust-root",
+        }
+    }
+}
+
+/// Where a setting got its value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    /// `pacman-conf` (DBPath)
+    PacmanConf,
+    File(PathBuf),
+    Env(&'static str),
+    Flag(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::PacmanConf => write!(f, "pacman-conf"),
+            Source::File(p) => write!(f, "{}", p.display()),
+            Source::Env(var) => write!(f, "env {var}"),
+            Source::Flag(flag) => write!(f, "flag {flag}"),
+        }
+    }
+}
+
+/// Config keys with the environment variables that set them (first listed
+/// wins), in `--show-config` order.
+const KEYS: &[(&str, &[&str])] = &[
+    ("pkgdest", &["PKGDEST"]),
+    ("snapshot_cache", &["YAORUST_SNAPSHOT_CACHE"]),
+    ("git_cache", &["YAORUST_GIT_CACHE"]),
+    ("fetch_backend", &["YAORUST_FETCH_BACKEND"]),
+    ("aur_url", &["YAORUST_AUR_URL"]),
+    ("aur_git_url", &["YAORUST_AUR_GIT_URL"]),
+    ("rpc_cache_ttl", &["YAORUST_RPC_CACHE_TTL"]),
+    ("pacman", &["YAORUST_PACMAN"]),
+    ("pacman_conf", &["YAORUST_PACMAN_CONF"]),
+    ("makepkg", &["YAORUST_MAKEPKG"]),
+    ("bsdtar", &["YAORUST_BSDTAR"]),
+    ("git", &["YAORUST_GIT"]),
+    ("diff", &["YAORUST_DIFF"]),
+    ("bwrap", &["YAORUST_BWRAP"]),
+    ("dbpath", &["YAORUST_DBPATH"]),
+    ("sudo", &["YAORUST_SUDO"]),
+    ("root_mode", &["YAORUST_ROOT_MODE"]),
+    ("auto_trust_root", &["YAORUST_AUTO_TRUST_ROOT"]),
+    ("build_user", &["YAORUST_BUILD_USER"]),
+    ("sandbox_net", &["YAORUST_SANDBOX_NET"]),
+    ("editor", &["YAORUST_EDITOR"]),
+    ("with_debug", &["YAORUST_WITH_DEBUG"]),
+    ("noconfirm", &["YAORUST_NOCONFIRM"]),
+    ("needed", &["YAORUST_NEEDED"]),
+    ("refresh", &[]),
+    ("verbose", &[]),
+];
+
+/// Keys a `[package.<pkgbase>]` section may override: the ones read while
+/// that pkgbase is fetched, reviewed and built.
+const PACKAGE_KEYS: &[&str] = &[
+    "fetch_backend",
+    "root_mode",
+    "auto_trust_root",
+    "build_user",
+    "sandbox_net",
+    "editor",
+    "with_debug",
+    "noconfirm",
+];
+
+/// One `[package.<pkgbase>]` setting.
+#[derive(Clone, Debug)]
+pub struct Override {
+    pub key: String,
+    pub value: String,
+    pub source: Source,
+}
+
+/// Runtime config, merged from all layers
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Where makepkg will place built packages
+    pub pkgdest: PathBuf,
+    /// How to behave when running as root
+    pub root_mode: RootMode,
+    /// Let Auto fall back to the patched makepkg when nothing safer works
+    pub auto_trust_root: bool,
+    /// Build user for "user" and "sandbox" modes
+    pub build_user: String,
+    /// Keep network access inside the build sandbox
+    pub sandbox_net: bool,
+    /// Snapshot cache dir for AUR tarballs
+    pub snapshot_cache: PathBuf,
+    /// cgit snapshots or persistent git clones
+    pub fetch_backend: FetchBackend,
+    /// Per-package clones for the git backend
+    pub git_cache: PathBuf,
+    /// Base URL of the AUR: RPC (`/rpc/`) and snapshots (`/cgit/`)
+    pub aur_url: String,
+    /// Base URL of the git repos (`{base}/{pkgbase}.git`); `aur_url` when unset
+    pub aur_git_url: Option<String>,
+    /// Seconds to keep AUR RPC info on disk (0 = in-memory only)
+    pub rpc_cache_ttl: u64,
+    /// Pacman binary name/path
+    pub pacman: String,
+    /// pacman-conf binary name/path (DBPath, repo order)
+    pub pacman_conf: String,
+    /// makepkg binary name/path
+    pub makepkg: String,
+    /// bsdtar binary name/path (snapshot extraction)
+    pub bsdtar: String,
+    /// git binary name/path (git backend)
+    pub git: String,
+    /// diff binary name/path (PKGBUILD review)
+    pub diff: String,
+    /// bubblewrap binary name/path (sandbox root mode)
+    pub bwrap: String,
+    /// pacman's DBPath (`local/` and `sync/*.db`), read directly
+    pub dbpath: PathBuf,
+    /// Sudo binary name/path
+    pub sudo: String,
+    /// PKGBUILD editor; VISUAL/EDITOR when unset
+    pub editor: Option<String>,
+    /// Ignore cached snapshots (--refresh)
+    pub refresh: bool,
+    /// Preferred provider per virtual dependency (`java-runtime` -> `jre-openjdk`)
+    pub providers: HashMap<String, String>,
+    /// Install -debug packages too, instead of only keeping them in PKGDEST
+    pub with_debug: bool,
+    /// Never prompt: take every default and pass --noconfirm on
+    pub noconfirm: bool,
+    /// Skip up-to-date targets instead of reinstalling them
+    pub needed: bool,
+    /// Verbose logging
+    pub verbose: bool,
+    /// Where each key (and `providers.<name>`) got its value
+    sources: BTreeMap<String, Source>,
+    /// `[package.<pkgbase>]` sections
+    packages: BTreeMap<String, Vec<Override>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            pkgdest: PathBuf::from("/var/cache/makepkg"),
+            root_mode: RootMode::Auto,
+            auto_trust_root: false,
+            build_user: "nobody".to_string(),
+            sandbox_net: true,
+            snapshot_cache: PathBuf::from("/var/cache/yaorust/snapshots"),
+            fetch_backend: FetchBackend::Snapshot,
+            git_cache: PathBuf::from("/var/cache/yaorust/git"),
+            aur_url: "https://aur.archlinux.org".to_string(),
+            aur_git_url: None,
+            rpc_cache_ttl: 0,
+            pacman: "pacman".to_string(),
+            pacman_conf: "pacman-conf".to_string(),
+            makepkg: "makepkg".to_string(),
+            bsdtar: "bsdtar".to_string(),
+            git: "git".to_string(),
+            diff: "diff".to_string(),
+            bwrap: "bwrap".to_string(),
+            dbpath: PathBuf::from("/var/lib/pacman"),
+            sudo: "sudo".to_string(),
+            editor: None,
+            refresh: false,
+            providers: HashMap::new(),
+            with_debug: false,
+            noconfirm: false,
+            needed: false,
+            verbose: false,
+            sources: BTreeMap::new(),
+            packages: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Load all layers. `flags` are the (key, flag) pairs given on the
+    /// command line; they only ever switch a setting on.
+    pub fn load(flags: &[(&'static str, &'static str)]) -> Result<Self> {
+        let mut files = vec![PathBuf::from(SYSTEM_CONFIG)];
+        files.extend(user_config());
+        let mut cfg = Self::load_from(&files, |var| env::var(var).ok(), flags)?;
+        if !cfg.sources.contains_key("dbpath")
+            && let Some(dbpath) = pacdb::default_dbpath(&cfg.pacman_conf)
+        {
+            cfg.dbpath = dbpath;
+            cfg.sources.insert("dbpath".into(), Source::PacmanConf);
+        }
+        Ok(cfg)
+    }
+
+    /// Layering without touching the real environment (for tests).
+    fn load_from(
+        files: &[PathBuf],
+        env: impl Fn(&str) -> Option<String>,
+        flags: &[(&'static str, &'static str)],
+    ) -> Result<Self> {
+        let mut cfg = Config::default();
+
+        for path in files {
+            let text = match fs::read_to_string(path) {
+                Ok(t) => t,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).context(format!("cannot read {}", path.display())),
+            };
+            let source = Source::File(path.clone());
+            for entry in parse_toml(&text).with_context(|| format!("in {}", path.display()))? {
+                let at = || format!("{}:{}", path.display(), entry.line);
+                match entry.section.as_slice() {
+                    [] => cfg.set_from(&entry.key, &entry.value, source.clone(), at)?,
+                    [s] if s == "providers" => {
+                        cfg.providers.insert(entry.key.clone(), entry.value);
+                        cfg.sources
+                            .insert(format!("providers.{}", entry.key), source.clone());
+                    }
+                    [s, pkg] if s == "package" => {
+                        if !PACKAGE_KEYS.contains(&entry.key.as_str()) {
+                            bail!("{}: {} cannot be set per package", at(), entry.key);
+                        }
+                        // validate now rather than halfway through a build
+                        Config::default()
+                            .set(&entry.key, &entry.value)
+                            .with_context(at)?;
+                        cfg.packages.entry(pkg.clone()).or_default().push(Override {
+                            key: entry.key,
+                            value: entry.value,
+                            source: source.clone(),
+                        });
+                    }
+                    _ => bail!("{}: unknown section [{}]", at(), entry.section.join(".")),
+                }
+            }
+        }
+
+        for (key, vars) in KEYS {
+            if let Some((var, value)) = vars.iter().find_map(|v| Some((*v, env(v)?))) {
+                cfg.set_from(key, &value, Source::Env(var), || format!("${var}"))?;
+            }
+        }
+        if let Some(value) = env("YAORUST_PROVIDERS") {
+            for (name, pkg) in providers::parse_preferences(&value) {
+                cfg.sources.insert(
+                    format!("providers.{name}"),
+                    Source::Env("YAORUST_PROVIDERS"),
+                );
+                cfg.providers.insert(name, pkg);
+            }
+        }
+
+        for (key, flag) in flags {
+            cfg.set_from(key, "true", Source::Flag(flag), || flag.to_string())?;
+        }
+        Ok(cfg)
+    }
+
+    fn set_from(
+        &mut self,
+        key: &str,
+        value: &str,
+        source: Source,
+        at: impl Fn() -> String,
+    ) -> Result<()> {
+        self.set(key, value)
+            .with_context(|| format!("{}: invalid {key}", at()))?;
+        self.sources.insert(key.to_string(), source);
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "pkgdest" => self.pkgdest = PathBuf::from(value),
+            "snapshot_cache" => self.snapshot_cache = PathBuf::from(value),
+            "git_cache" => self.git_cache = PathBuf::from(value),
+            "fetch_backend" => {
+                self.fetch_backend = match value {
+                    "snapshot" => FetchBackend::Snapshot,
+                    "git" => FetchBackend::Git,
+                    _ => bail!("expected snapshot or git, got {value:?}"),
+                }
+            }
+            "aur_url" => self.aur_url = value.trim_end_matches('/').to_string(),
+            "aur_git_url" => {
+                self.aur_git_url =
+                    Some(value.trim_end_matches('/').to_string()).filter(|u| !u.is_empty())
+            }
+            "rpc_cache_ttl" => {
+                self.rpc_cache_ttl = value
+                    .parse()
+                    .with_context(|| format!("expected seconds, got {value:?}"))?
+            }
+            "pacman" => self.pacman = value.to_string(),
+            "pacman_conf" => self.pacman_conf = value.to_string(),
+            "makepkg" => self.makepkg = value.to_string(),
+            "bsdtar" => self.bsdtar = value.to_string(),
+            "git" => self.git = value.to_string(),
+            "diff" => self.diff = value.to_string(),
+            "bwrap" => self.bwrap = value.to_string(),
+            "dbpath" => self.dbpath = PathBuf::from(value),
+            "sudo" => self.sudo = value.to_string(),
+            "root_mode" => {
+                self.root_mode = RootMode::parse(value).with_context(|| {
+                    format!("expected auto, sandbox, user or trust-root, got {value:?}")
+                })?
+            }
+            "auto_trust_root" => self.auto_trust_root = parse_bool(value)?,
+            "build_user" => self.build_user = value.to_string(),
+            "sandbox_net" => self.sandbox_net = parse_bool(value)?,
+            "editor" => self.editor = Some(value.to_string()).filter(|e| !e.trim().is_empty()),
+            "with_debug" => self.with_debug = parse_bool(value)?,
+            "noconfirm" => self.noconfirm = parse_bool(value)?,
+            "needed" => self.needed = parse_bool(value)?,
+            "refresh" => self.refresh = parse_bool(value)?,
+            "verbose" => self.verbose = parse_bool(value)?,
+            _ => bail!("unknown setting"),
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> String {
+        let path = |p: &Path| p.display().to_string();
+        match key {
+            "pkgdest" => path(&self.pkgdest),
+            "snapshot_cache" => path(&self.snapshot_cache),
+            "git_cache" => path(&self.git_cache),
+            "fetch_backend" => match self.fetch_backend {
+                FetchBackend::Snapshot => "snapshot".into(),
+                FetchBackend::Git => "git".into(),
+            },
+            "aur_url" => self.aur_url.clone(),
+            "aur_git_url" => self.git_url().to_string(),
+            "rpc_cache_ttl" => self.rpc_cache_ttl.to_string(),
+            "pacman" => self.pacman.clone(),
+            "pacman_conf" => self.pacman_conf.clone(),
+            "makepkg" => self.makepkg.clone(),
+            "bsdtar" => self.bsdtar.clone(),
+            "git" => self.git.clone(),
+            "diff" => self.diff.clone(),
+            "bwrap" => self.bwrap.clone(),
+            "dbpath" => path(&self.dbpath),
+            "sudo" => self.sudo.clone(),
+            "root_mode" => self.root_mode.as_str().into(),
+            "auto_trust_root" => self.auto_trust_root.to_string(),
+            "build_user" => self.build_user.clone(),
+            "sandbox_net" => self.sandbox_net.to_string(),
+            "editor" => self.editor.clone().unwrap_or_default(),
+            "with_debug" => self.with_debug.to_string(),
+            "noconfirm" => self.noconfirm.to_string(),
+            "needed" => self.needed.to_string(),
+            "refresh" => self.refresh.to_string(),
+            "verbose" => self.verbose.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Base URL the git backend clones from.
+    pub fn git_url(&self) -> &str {
+        self.aur_git_url.as_deref().unwrap_or(&self.aur_url)
+    }
+
+    /// The config to build `pkgbase` with: its `[package.<pkgbase>]`
+    /// overrides applied on top of the file layers. Environment variables
+    /// and flags still win.
+    pub fn for_package(&self, pkgbase: &str) -> Config {
+        let mut cfg = self.clone();
+        for o in self.packages.get(pkgbase).into_iter().flatten() {
+            if matches!(
+                self.sources.get(&o.key),
+                Some(Source::Env(_) | Source::Flag(_))
+            ) {
+                continue;
+            }
+            // validated while loading
+            let _ = cfg.set(&o.key, &o.value);
+            cfg.sources.insert(o.key.clone(), o.source.clone());
+        }
+        cfg
+    }
+
+    /// `--show-config`: every effective value and where it came from.
+    pub fn print(&self) {
+        let row = |key: &str, value: &str, source: &Source| {
+            println!("{key:<16} = {:<40} ({source})", quote(value));
+        };
+        for (key, _) in KEYS {
+            row(
+                key,
+                &self.get(key),
+                self.sources.get(*key).unwrap_or(&Source::Default),
+            );
+        }
+        if !self.providers.is_empty() {
+            println!("\n[providers]");
+            let mut names: Vec<&String> = self.providers.keys().collect();
+            names.sort();
+            for name in names {
+                let source = &self.sources[&format!("providers.{name}")];
+                row(name, &self.providers[name], source);
+            }
+        }
+        for (pkg, overrides) in &self.packages {
+            println!("\n[package.{pkg}]");
+            for o in overrides {
+                row(&o.key, &o.value, &o.source);
+            }
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/yaorust/config.toml`, else `~/.config/yaorust/config.toml`.
+fn user_config() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".config")))?;
+    Some(base.join("yaorust").join("config.toml"))
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => bail!("expected true or false, got {value:?}"),
+    }
+}
+
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.contains(char::is_whitespace) {
+        format!("{value:?}")
+    } else {
+        value.to_string()
+    }
+}
+
+/* ---- TOML subset ---- */
+
+/// One `key = value` line, under the `[a.b]` section it appeared in.
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    section: Vec<String>,
+    key: String,
+    /// Strings unquoted; booleans and integers as written
+    value: String,
+    line: usize,
+}
+
+/// Parse the TOML subset the config files use: `[section]` headers with
+/// dotted (optionally quoted) names, and `key = value` pairs whose value is
+/// a string, boolean or integer. `#` starts a comment.
+fn parse_toml(text: &str) -> Result<Vec<Entry>> {
+    let mut out = Vec::new();
+    let mut section: Vec<String> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let n = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('[') {
+            let (names, tail) = parse_keys(rest, ']').with_context(|| format!("line {n}"))?;
+            if !is_comment(tail) {
+                bail!("line {n}: unexpected {tail:?} after section header");
+            }
+            section = names;
+            continue;
+        }
+        let (keys, rest) = parse_keys(line, '=').with_context(|| format!("line {n}"))?;
+        let [key] = &keys[..] else {
+            bail!("line {n}: dotted keys are not supported");
+        };
+        let (value, tail) = parse_value(rest.trim_start()).with_context(|| format!("line {n}"))?;
+        if !is_comment(tail) {
+            bail!("line {n}: unexpected {tail:?} after value");
+        }
+        out.push(Entry {
+            section: section.clone(),
+            key: key.clone(),
+            value,
+            line: n,
+        });
+    }
+    Ok(out)
+}
+
+fn is_comment(s: &str) -> bool {
+    let s = s.trim();
+    s.is_empty() || s.starts_with('#')
+}
+
+/// Dot-separated bare or quoted keys up to `end`; returns them and the rest.
+fn parse_keys(s: &str, end: char) -> Result<(Vec<String>, &str)> {
+    let mut keys = Vec::new();
+    let mut rest = s.trim_start();
+    loop {
+        let key;
+        if rest.starts_with('"') || rest.starts_with('\'') {
+            (key, rest) = parse_string(rest)?;
+        } else {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(rest.len());
+            if len == 0 {
+                bail!("expected a key");
+            }
+            (key, rest) = (rest[..len].to_string(), &rest[len..]);
+        }
+        keys.push(key);
+        rest = rest.trim_start();
+        if let Some(r) = rest.strip_prefix('.') {
+            rest = r.trim_start();
+        } else if let Some(r) = rest.strip_prefix(end) {
+            return Ok((keys, r));
+        } else {
+            bail!("expected `{end}`");
+        }
+    }
+}
+
+fn parse_value(s: &str) -> Result<(String, &str)> {
+    if s.starts_with('"') || s.starts_with('\'') {
+        return parse_string(s);
+    }
+    let len = s
+        .find(|c: char| c.is_whitespace() || c == '#')
+        .unwrap_or(s.len());
+    let word = &s[..len];
+    if word == "true" || word == "false" || (!word.is_empty() && word.parse::<i64>().is_ok()) {
+        return Ok((word.to_string(), &s[len..]));
+    }
+    bail!("unsupported value {word:?} (expected a string, boolean or integer)");
+}
+
+/// A `"basic"` (with escapes) or `'literal'` string; returns it and the rest.
+fn parse_string(s: &str) -> Result<(String, &str)> {
+    let quote = s.chars().next().unwrap_or('"');
+    let mut out = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((out, &s[i + 1..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, c)| c) {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                other => bail!("unsupported escape \\{}", other.unwrap_or(' ')),
+            },
+            c => out.push(c),
+        }
+    }
+    bail!("unterminated string");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_toml_subset() {
+        let text = r#"
+# system defaults
+root_mode = "sandbox"   # comment
+sandbox_net = false
+rpc_cache_ttl = 300
+
+[providers]
+java-runtime = 'jre-openjdk'
+
+[package."foo.bar"]
+editor = "code --wait"
+"#;
+        let entries = parse_toml(text).unwrap();
+        let flat: Vec<(String, &str, &str)> = entries
+            .iter()
+            .map(|e| (e.section.join("/"), e.key.as_str(), e.value.as_str()))
+            .collect();
+        assert_eq!(
+            flat,
+            [
+                ("".to_string(), "root_mode", "sandbox"),
+                ("".to_string(), "sandbox_net", "false"),
+                ("".to_string(), "rpc_cache_ttl", "300"),
+                ("providers".to_string(), "java-runtime", "jre-openjdk"),
+                ("package/foo.bar".to_string(), "editor", "code --wait"),
+            ]
+        );
+        assert_eq!(entries[1].line, 4);
+
+        assert!(parse_toml("key = [1, 2]").is_err());
+        assert!(parse_toml("key = \"open").is_err());
+        assert!(parse_toml("a.b = 1").is_err());
+    }
+
+    #[test]
+    fn layers_in_precedence_order() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (system, user) = (
+            tmp.path().join("yaorust.conf"),
+            tmp.path().join("config.toml"),
+        );
+        fs::write(
+            &system,
+            "root_mode = \"user\"\nbuild_user = \"builder\"\npkgdest = \"/srv/pkgs\"\n\
+             [package.chromium]\nroot_mode = \"trust-root\"\nnoconfirm = false\n",
+        )
+        .unwrap();
+        fs::write(&user, "build_user = \"me\"\nneeded = true\n").unwrap();
+        let env = |var: &str| match var {
+            "PKGDEST" => Some("/tmp/pkgs".to_string()),
+            "YAORUST_AUR_URL" => Some("http://localhost:8080/".to_string()),
+            "YAORUST_AUR_GIT_URL" => Some("file:///srv/aur/".to_string()),
+            _ => None,
+        };
+
+        let cfg = Config::load_from(
+            &[
+                system.clone(),
+                user.clone(),
+                tmp.path().join("missing.toml"),
+            ],
+            env,
+            &[("noconfirm", "--noconfirm")],
+        )
+        .unwrap();
+        assert!(matches!(cfg.root_mode, RootMode::User));
+        assert_eq!(cfg.sources["root_mode"], Source::File(system.clone()));
+        assert_eq!(cfg.build_user, "me");
+        assert_eq!(cfg.sources["build_user"], Source::File(user));
+        assert_eq!(cfg.pkgdest, Path::new("/tmp/pkgs"));
+        assert_eq!(cfg.sources["pkgdest"], Source::Env("PKGDEST"));
+        assert_eq!(cfg.aur_url, "http://localhost:8080");
+        assert_eq!(cfg.git_url(), "file:///srv/aur");
+        assert_eq!(Config::default().git_url(), "https://aur.archlinux.org");
+        assert!(cfg.needed && cfg.noconfirm);
+        assert_eq!(cfg.sources["noconfirm"], Source::Flag("--noconfirm"));
+        assert!(!cfg.sources.contains_key("sudo"));
+
+        let chromium = cfg.for_package("chromium");
+        assert!(matches!(chromium.root_mode, RootMode::TrustRoot));
+        assert!(matches!(cfg.for_package("other").root_mode, RootMode::User));
+        // per-package values only replace file values
+        assert!(chromium.noconfirm);
+        assert_eq!(chromium.sources["noconfirm"], Source::Flag("--noconfirm"));
+
+        fs::write(&system, "root_mode = \"yolo\"\n").unwrap();
+        assert!(Config::load_from(std::slice::from_ref(&system), |_| None, &[]).is_err());
+        fs::write(&system, "[package.x]\npkgdest = \"/x\"\n").unwrap();
+        assert!(Config::load_from(&[system], |_| None, &[]).is_err());
+    }
+}
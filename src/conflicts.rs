@@ -0,0 +1,206 @@
+//! Conflict checks before anything is built.
+//!
+//! pacman only looks for conflicts when `pacman -U` runs, i.e. after the
+//! build. We evaluate `conflicts`, `replaces` and `provides` from the AUR
+//! metadata against the installed packages and the rest of the plan up-front,
+//! ask pacman's questions right away, and hand the confirmed removals to the
+//! install step.
+
+use anyhow::{Result, bail};
+
+use crate::aur::{AurPkg, AurSource};
+use crate::config::Config;
+use crate::deps::Plan;
+use crate::error::YaoError;
+use crate::pacman::PackageDb;
+use crate::pacman::db::DbPkg;
+use crate::pacman::vercmp::Dep;
+use crate::ui::prompt_yes_no_default;
+
+/// An installed package the user agreed to remove for a plan package.
+#[derive(Debug, Clone)]
+pub struct Removal {
+    /// Installed package to remove
+    pub name: String,
+    /// Plan package that conflicts with or replaces it
+    pub by: String,
+    /// Listed in `replaces` (rather than only in conflict)
+    pub replaces: bool,
+}
+
+/// Check the AUR packages of `plan`, prompting for every conflict found.
+/// A conflict with another package of the plan (AUR or repo) is an error.
+pub fn check(
+    cfg: &Config,
+    aur: &dyn AurSource,
+    db: &dyn PackageDb,
+    plan: &Plan,
+) -> Result<Vec<Removal>> {
+    let names: Vec<String> = plan.steps.iter().map(|s| s.name.clone()).collect();
+    let new = aur.multiinfo(&names)?;
+
+    // Two plan packages in conflict: nothing sensible to remove
+    for (i, a) in new.iter().enumerate() {
+        for b in &new[i + 1..] {
+            if let Some(reason) = aur_conflict(a, b) {
+                bail!(YaoError::Conflict(format!(
+                    "unresolvable package conflicts detected: {} and {} are in conflict ({reason})",
+                    a.name, b.name
+                )));
+            }
+        }
+    }
+    let repo: Vec<&DbPkg> = plan
+        .repo_targets
+        .iter()
+        .chain(plan.repo_deps.iter().map(|s| &s.name))
+        .filter_map(|name| sync_pkg(db, name))
+        .collect();
+    for n in &new {
+        for r in &repo {
+            if let Some(reason) = local_conflict(n, r) {
+                bail!(YaoError::Conflict(format!(
+                    "unresolvable package conflicts detected: {} and {} are in conflict ({reason})",
+                    n.name, r.name
+                )));
+            }
+        }
+    }
+
+    let mut removals: Vec<Removal> = Vec::new();
+    for n in &new {
+        for i in db.local_pkgs() {
+            // upgrades/reinstalls of the same name are not conflicts
+            if i.name == n.name
+                || names.contains(&i.name)
+                || removals.iter().any(|r| r.name == i.name)
+            {
+                continue;
+            }
+            let replaces = n
+                .replaces
+                .iter()
+                .any(|d| Dep::parse(d).satisfied_by_pkg(&i.name, &i.version, &[]));
+            if replaces {
+                let q = format!(":: Replace {} with aur/{}? [Y/n] ", i.name, n.name);
+                if prompt_yes_no_default(cfg, &q, true)? {
+                    removals.push(Removal {
+                        name: i.name.clone(),
+                        by: n.name.clone(),
+                        replaces: true,
+                    });
+                    continue;
+                }
+            }
+            let Some(reason) = local_conflict(n, i) else {
+                continue;
+            };
+            let q = if reason == i.name || reason == n.name {
+                format!(
+                    ":: {} and {} are in conflict. Remove {}? [y/N] ",
+                    n.name, i.name, i.name
+                )
+            } else {
+                format!(
+                    ":: {} and {} are in conflict ({reason}). Remove {}? [y/N] ",
+                    n.name, i.name, i.name
+                )
+            };
+            if !prompt_yes_no_default(cfg, &q, false)? {
+                // --noconfirm answered no for the user: nothing was declined
+                if cfg.noconfirm {
+                    bail!(YaoError::Conflict(format!(
+                        "unresolvable package conflicts detected: {} and {} are in conflict \
+                         (not removing {} with --noconfirm)",
+                        n.name, i.name, i.name
+                    )));
+                }
+                eprintln!(":: Aborted by user.");
+                bail!(YaoError::Aborted);
+            }
+            removals.push(Removal {
+                name: i.name.clone(),
+                by: n.name.clone(),
+                replaces: false,
+            });
+        }
+    }
+    Ok(removals)
+}
+
+/// The sync package a plan entry (`pkg` or `repo/pkg`) stands for.
+fn sync_pkg<'a>(db: &'a dyn PackageDb, name: &str) -> Option<&'a DbPkg> {
+    match name.split_once('/') {
+        Some((repo, name)) => db
+            .sync_pkgs()
+            .find(|(r, p)| *r == repo && p.name == name)
+            .map(|(_, p)| p),
+        None => db.sync(name).map(|(_, p)| p),
+    }
+}
+
+/// The conflicting dependency name if `a` and `b` cannot be installed together.
+fn aur_conflict(a: &AurPkg, b: &AurPkg) -> Option<String> {
+    let hit = |x: &AurPkg, y: &AurPkg| {
+        x.conflicts
+            .iter()
+            .map(|c| Dep::parse(c))
+            .find(|c| c.satisfied_by_pkg(&y.name, &y.version, &y.provides))
+            .map(|c| c.name)
+    };
+    hit(a, b).or_else(|| hit(b, a))
+}
+
+/// Same as `aur_conflict`, for a plan package and an installed or repo one.
+fn local_conflict(n: &AurPkg, i: &DbPkg) -> Option<String> {
+    n.conflicts
+        .iter()
+        .map(|c| Dep::parse(c))
+        .find(|c| c.satisfied_by_pkg(&i.name, &i.version, &i.provides))
+        .or_else(|| {
+            i.conflicts
+                .iter()
+                .map(|c| Dep::parse(c))
+                .find(|c| c.satisfied_by_pkg(&n.name, &n.version, &n.provides))
+        })
+        .map(|c| c.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aur(name: &str, conflicts: &[&str], provides: &[&str]) -> AurPkg {
+        serde_json::from_value(serde_json::json!({
+            "Name": name,
+            "Version": "1.0-1",
+            "Conflicts": conflicts,
+            "Provides": provides,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn finds_conflicts_both_ways() {
+        let git = aur("foo-git", &["foo"], &["foo"]);
+        let local = DbPkg {
+            name: "foo".into(),
+            version: "0.9-1".into(),
+            ..Default::default()
+        };
+        assert_eq!(local_conflict(&git, &local).as_deref(), Some("foo"));
+
+        let bar = aur("bar", &[], &["libbar.so"]);
+        let old = DbPkg {
+            name: "libbar-compat".into(),
+            version: "1-1".into(),
+            conflicts: vec!["libbar.so".into()],
+            ..Default::default()
+        };
+        assert_eq!(local_conflict(&bar, &old).as_deref(), Some("libbar.so"));
+        assert!(local_conflict(&aur("baz", &["foo<0.5"], &[]), &local).is_none());
+
+        assert!(aur_conflict(&git, &aur("foo", &[], &[])).is_some());
+        assert!(aur_conflict(&git, &bar).is_none());
+    }
+}
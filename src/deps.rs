@@ -0,0 +1,431 @@
+//! Recursive AUR dependency resolution.
+//!
+//! makepkg's `--syncdeps` only installs dependencies available in the sync
+//! repos, so anything that lives in the AUR has to be built and installed by
+//! us first. The resolver walks depends/makedepends/checkdepends of every AUR
+//! target and returns a build plan where dependencies come before dependents.
+//! Virtual dependencies nobody installed provides yet go through provider
+//! selection; a repo provider chosen there is installed before the builds so
+//! makepkg does not ask again.
+
+use anyhow::{Result, bail};
+use std::collections::{HashMap, HashSet};
+
+use crate::aur::{AurPkg, AurSource};
+use crate::config::Config;
+use crate::conflicts::Removal;
+use crate::error::YaoError;
+use crate::pacman::PackageDb;
+use crate::pacman::vercmp::Dep;
+use crate::providers::Providers;
+use crate::targets::{PkgKind, classify};
+
+/// One AUR package to build, in build order.
+#[derive(Debug, Clone)]
+pub struct BuildStep {
+    pub name: String,
+    /// Pulled in as a dependency (installed with `--asdeps`)
+    pub as_dep: bool,
+    /// First package that required this one (for the plan display)
+    pub required_by: Option<String>,
+}
+
+/// Resolved AUR builds plus the repo providers picked for virtual deps.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    pub steps: Vec<BuildStep>,
+    /// Repo packages to install `--asdeps` before building
+    pub repo_deps: Vec<BuildStep>,
+    /// Repo targets (`pkg` or `repo/pkg`) installed with the same run
+    pub repo_targets: Vec<String>,
+    /// Installed packages to remove for conflicts/replaces (filled by conflicts::check)
+    pub removals: Vec<Removal>,
+}
+
+/// The plan steps built from one pkgbase (split packages are built once).
+#[derive(Debug, Clone)]
+pub struct BaseBuild {
+    pub pkgbase: String,
+    /// Requested or required members, in plan order
+    pub members: Vec<BuildStep>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Visiting,
+    Done,
+}
+
+struct Resolver<'a> {
+    aur: &'a dyn AurSource,
+    db: &'a dyn PackageDb,
+    targets: HashSet<String>,
+    marks: HashMap<String, Mark>,
+    /// RPC info of every AUR package visited so far
+    info: HashMap<String, AurPkg>,
+    /// DFS stack, used to report cycles
+    stack: Vec<String>,
+    order: Vec<BuildStep>,
+    providers: Providers<'a>,
+    /// virtual dependency -> chosen provider name
+    chosen: HashMap<String, String>,
+    repo_deps: Vec<BuildStep>,
+}
+
+/// Resolve AUR dependencies of `targets` into a topologically ordered build plan.
+///
+/// Dependencies already satisfied locally or available in the repos are left
+/// to makepkg; only AUR packages end up in the plan.
+pub fn resolve_aur_deps(
+    cfg: &Config,
+    aur: &dyn AurSource,
+    db: &dyn PackageDb,
+    targets: &[String],
+) -> Result<Plan> {
+    let mut r = Resolver {
+        aur,
+        db,
+        targets: targets.iter().cloned().collect(),
+        marks: HashMap::new(),
+        info: HashMap::new(),
+        stack: Vec::new(),
+        order: Vec::new(),
+        providers: Providers::new(cfg, aur, db),
+        chosen: HashMap::new(),
+        repo_deps: Vec::new(),
+    };
+    aur.prefetch(targets)?;
+    for t in targets {
+        r.visit(t, None)?;
+    }
+    Ok(Plan {
+        steps: r.order,
+        repo_deps: r.repo_deps,
+        repo_targets: Vec::new(),
+        removals: Vec::new(),
+    })
+}
+
+impl Resolver<'_> {
+    fn visit(&mut self, name: &str, parent: Option<&str>) -> Result<()> {
+        match self.marks.get(name) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                let start = self.stack.iter().position(|n| n == name).unwrap_or(0);
+                let mut cycle = self.stack[start..].to_vec();
+                cycle.push(name.to_string());
+                bail!("dependency cycle detected: {}", cycle.join(" -> "));
+            }
+            None => {}
+        }
+
+        let pkg: AurPkg = match self.aur.info(name)? {
+            Some(p) => p,
+            None => match parent {
+                Some(parent) => bail!(YaoError::NotFound(format!(
+                    "{name} (required by {parent}) not found in AUR"
+                ))),
+                None => bail!(YaoError::NotFound(format!("{name} not found in AUR"))),
+            },
+        };
+
+        self.marks.insert(name.to_string(), Mark::Visiting);
+        self.stack.push(name.to_string());
+
+        let unsatisfied: Vec<&String> = pkg
+            .depends
+            .iter()
+            .chain(&pkg.make_depends)
+            .chain(&pkg.check_depends)
+            .filter(|dep| !self.db.dep_satisfied(dep))
+            .collect();
+        // one batched RPC request for this level instead of one per dependency
+        let names: Vec<String> = unsatisfied.iter().map(|d| Dep::parse(d).name).collect();
+        self.aur.prefetch(&names)?;
+
+        for dep in unsatisfied {
+            let want = Dep::parse(dep);
+            let mut target = want.name.clone();
+            if let Some(provider) = self.chosen.get(&want.name) {
+                target = provider.clone();
+            }
+            let known = self.marks.contains_key(&target)
+                || self.targets.contains(&target)
+                || self.repo_deps.iter().any(|s| s.name == target);
+            if !known {
+                match classify(self.db, self.aur, &target) {
+                    // makepkg --syncdeps installs repo deps itself
+                    Ok(PkgKind::Repo) => continue,
+                    Ok(PkgKind::Aur) => {}
+                    // a group never satisfies a dependency
+                    Ok(PkgKind::Group) => {
+                        bail!("cannot resolve dependency {dep} of {name}: {target} is a group")
+                    }
+                    // no package by that name: a virtual dependency?
+                    Err(e) => {
+                        let Some(p) = self.providers.choose(&want.name)? else {
+                            return Err(
+                                e.context(format!("cannot resolve dependency {dep} of {name}"))
+                            );
+                        };
+                        self.chosen.insert(want.name.clone(), p.name.clone());
+                        if p.repo.is_some() {
+                            self.repo_deps.push(BuildStep {
+                                name: p.name,
+                                as_dep: true,
+                                required_by: Some(name.to_string()),
+                            });
+                            continue;
+                        }
+                        target = p.name;
+                    }
+                }
+            } else if self.repo_deps.iter().any(|s| s.name == target) {
+                continue;
+            }
+            self.visit(&target, Some(name))?;
+            // a provider's own version says nothing about the provided one
+            if target == want.name
+                && let Some(p) = self.info.get(&want.name)
+                && !want.satisfied_by(&p.version)
+            {
+                bail!(
+                    "{dep} (required by {name}) cannot be satisfied: AUR has {} {}",
+                    p.name,
+                    p.version
+                );
+            }
+        }
+
+        self.info.insert(name.to_string(), pkg);
+        self.stack.pop();
+        self.marks.insert(name.to_string(), Mark::Done);
+        // rebuilding an explicitly installed package for a version bump
+        // must not demote it to a dependency
+        let explicit = self.db.local(name).is_some_and(|p| !p.as_dep);
+        self.order.push(BuildStep {
+            name: name.to_string(),
+            as_dep: !self.targets.contains(name) && !explicit,
+            required_by: parent.map(str::to_string),
+        });
+        Ok(())
+    }
+}
+
+/// Merge plan steps that share a pkgbase into one build.
+///
+/// Merging can pull a member ahead of a step it depends on (`lib-cli`,
+/// `x`, `lib-gui` where lib-gui needs x), so the builds are ordered again:
+/// each one after every build that provides one of its members' dependencies,
+/// otherwise keeping the plan order of their first members.
+pub fn group_by_pkgbase(aur: &dyn AurSource, plan: Vec<BuildStep>) -> Result<Vec<BaseBuild>> {
+    let mut bases: Vec<BaseBuild> = Vec::new();
+    // plan step -> (index into bases, its RPC record)
+    let mut steps: Vec<(usize, Option<AurPkg>)> = Vec::new();
+    for step in plan {
+        let info = aur.info(&step.name)?;
+        let pkgbase = info
+            .as_ref()
+            .and_then(|p| p.package_base.clone())
+            .unwrap_or_else(|| step.name.clone());
+        let i = match bases.iter().position(|b| b.pkgbase == pkgbase) {
+            Some(i) => i,
+            None => {
+                bases.push(BaseBuild {
+                    pkgbase,
+                    members: Vec::new(),
+                });
+                bases.len() - 1
+            }
+        };
+        bases[i].members.push(step);
+        steps.push((i, info));
+    }
+
+    // base -> the other bases it needs built first
+    let mut needs: Vec<HashSet<usize>> = vec![HashSet::new(); bases.len()];
+    for (i, info) in &steps {
+        let Some(info) = info else { continue };
+        let deps = info
+            .depends
+            .iter()
+            .chain(&info.make_depends)
+            .chain(&info.check_depends)
+            .map(|d| Dep::parse(d).name);
+        for dep in deps {
+            let by = steps.iter().find(|(_, p)| {
+                p.as_ref().is_some_and(|p| {
+                    p.name == dep || p.provides.iter().any(|v| Dep::parse(v).name == dep)
+                })
+            });
+            if let Some((j, _)) = by
+                && j != i
+            {
+                needs[*i].insert(*j);
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = Vec::new();
+    while order.len() < bases.len() {
+        let Some(next) = (0..bases.len())
+            .find(|i| !order.contains(i) && needs[*i].iter().all(|j| order.contains(j)))
+        else {
+            let left: Vec<&str> = (0..bases.len())
+                .filter(|i| !order.contains(i))
+                .map(|i| bases[i].pkgbase.as_str())
+                .collect();
+            bail!("dependency cycle between pkgbases: {}", left.join(", "));
+        };
+        order.push(next);
+    }
+    let mut bases: Vec<Option<BaseBuild>> = bases.into_iter().map(Some).collect();
+    Ok(order.into_iter().filter_map(|i| bases[i].take()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::Fetched;
+    use crate::pacman::db::DbPkg;
+    use std::path::Path;
+
+    /// An AUR with a fixed set of packages; fetching is never needed here.
+    struct FakeAur(Vec<AurPkg>);
+
+    impl AurSource for FakeAur {
+        fn multiinfo(&self, names: &[String]) -> Result<Vec<AurPkg>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|p| names.contains(&p.name))
+                .cloned()
+                .collect())
+        }
+
+        fn search(&self, _term: &str) -> Result<Vec<AurPkg>> {
+            Ok(Vec::new())
+        }
+
+        fn search_provides(&self, _name: &str) -> Result<Vec<AurPkg>> {
+            Ok(Vec::new())
+        }
+
+        fn fetch(&self, _cfg: &Config, name: &str, _dest: &Path) -> Result<Fetched> {
+            bail!("unexpected fetch of {name}")
+        }
+    }
+
+    /// Installed packages plus a single `core` repo.
+    #[derive(Default)]
+    struct FakeDb {
+        local: Vec<DbPkg>,
+        core: Vec<DbPkg>,
+    }
+
+    impl PackageDb for FakeDb {
+        fn local(&self, name: &str) -> Option<&DbPkg> {
+            self.local.iter().find(|p| p.name == name)
+        }
+
+        fn local_pkgs(&self) -> &[DbPkg] {
+            &self.local
+        }
+
+        fn sync_pkgs(&self) -> Box<dyn Iterator<Item = (&str, &DbPkg)> + '_> {
+            Box::new(self.core.iter().map(|p| ("core", p)))
+        }
+    }
+
+    fn rpc(name: &str, base: &str, depends: &[&str]) -> AurPkg {
+        serde_json::from_value(serde_json::json!({
+            "Name": name,
+            "PackageBase": base,
+            "Version": "1.0-1",
+            "Depends": depends,
+        }))
+        .unwrap()
+    }
+
+    fn pkg(name: &str) -> DbPkg {
+        DbPkg {
+            name: name.into(),
+            version: "1.0-1".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dependencies_build_first_and_split_bases_merge() {
+        let aur = FakeAur(vec![
+            rpc("app", "app", &["lib-cli", "glibc", "zlib"]),
+            rpc("lib-cli", "lib", &["lib-common"]),
+            rpc("lib-common", "lib", &[]),
+        ]);
+        let db = FakeDb {
+            local: vec![pkg("glibc")],
+            core: vec![pkg("glibc"), pkg("zlib")],
+        };
+        let plan = resolve_aur_deps(&Config::default(), &aur, &db, &["app".into()]).unwrap();
+        let steps: Vec<(&str, bool, Option<&str>)> = plan
+            .steps
+            .iter()
+            .map(|s| (s.name.as_str(), s.as_dep, s.required_by.as_deref()))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                ("lib-common", true, Some("lib-cli")),
+                ("lib-cli", true, Some("app")),
+                ("app", false, None),
+            ]
+        );
+        assert!(plan.repo_deps.is_empty());
+
+        let builds = group_by_pkgbase(&aur, plan.steps).unwrap();
+        let bases: Vec<&str> = builds.iter().map(|b| b.pkgbase.as_str()).collect();
+        assert_eq!(bases, ["lib", "app"]);
+        assert_eq!(builds[0].members.len(), 2);
+    }
+
+    #[test]
+    fn split_member_after_its_dependency() {
+        // lib-gui needs x, which the plan puts between the two lib members
+        let aur = FakeAur(vec![
+            rpc("app", "app", &["lib-cli", "lib-gui"]),
+            rpc("lib-cli", "lib", &[]),
+            rpc("x", "x", &[]),
+            rpc("lib-gui", "lib", &["x"]),
+        ]);
+        let db = FakeDb::default();
+        let plan = resolve_aur_deps(&Config::default(), &aur, &db, &["app".into()]).unwrap();
+        let names: Vec<&str> = plan.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["lib-cli", "x", "lib-gui", "app"]);
+
+        let builds = group_by_pkgbase(&aur, plan.steps).unwrap();
+        let bases: Vec<&str> = builds.iter().map(|b| b.pkgbase.as_str()).collect();
+        assert_eq!(bases, ["x", "lib", "app"]);
+
+        // lib-gui needs x, x needs lib-cli: no order of whole pkgbases works
+        let aur = FakeAur(vec![
+            rpc("lib-cli", "lib", &[]),
+            rpc("x", "x", &["lib-cli"]),
+            rpc("lib-gui", "lib", &["x"]),
+        ]);
+        let plan = resolve_aur_deps(&Config::default(), &aur, &db, &["lib-gui".into()]).unwrap();
+        let err = group_by_pkgbase(&aur, plan.steps).unwrap_err();
+        assert_eq!(err.to_string(), "dependency cycle between pkgbases: lib, x");
+    }
+
+    #[test]
+    fn reports_cycles_and_missing_packages() {
+        let aur = FakeAur(vec![rpc("a", "a", &["b"]), rpc("b", "b", &["a"])]);
+        let db = FakeDb::default();
+        let err = resolve_aur_deps(&Config::default(), &aur, &db, &["a".into()]).unwrap_err();
+        assert_eq!(err.to_string(), "dependency cycle detected: a -> b -> a");
+
+        let aur = FakeAur(vec![rpc("a", "a", &[])]);
+        let err = resolve_aur_deps(&Config::default(), &aur, &db, &["c".into()]).unwrap_err();
+        assert_eq!(err.to_string(), "c not found in AUR");
+    }
+}
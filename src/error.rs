@@ -0,0 +1,104 @@
+//! Failure kinds that `yao` reports through its exit code.
+//!
+//! Errors stay `anyhow::Error` throughout; the failures scripts need to tell
+//! apart are raised as a [`YaoError`] (`bail!(YaoError::NotFound(..))`) or
+//! wrap the underlying error as context
+//! (`.context(YaoError::Build(..))`). [`exit_code`] picks the outermost one.
+//!
+//! | code | meaning                                                |
+//! |------|--------------------------------------------------------|
+//! | 0    | success (also "nothing to do")                         |
+//! | 1    | any other error                                        |
+//! | 2    | command line not understood (from clap)                |
+//! | 3    | [`YaoError::Config`]: config file, env or flags        |
+//! | 4    | [`YaoError::Permission`]: no safe way to build as root |
+//! | 5    | [`YaoError::NotFound`]: package not in repos or AUR    |
+//! | 6    | [`YaoError::Network`]: download or connection failed   |
+//! | 7    | [`YaoError::AurRpc`]: the AUR RPC answered an error    |
+//! | 8    | [`YaoError::Extract`]: snapshot or clone unusable      |
+//! | 9    | [`YaoError::Build`]: makepkg failed                    |
+//! | 10   | [`YaoError::Install`]: pacman failed                   |
+//! | 11   | [`YaoError::Aborted`]: the user said no                |
+//! | 12   | [`YaoError::Conflict`]: conflicts left unresolved      |
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum YaoError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Network(String),
+    #[error("{0}")]
+    AurRpc(String),
+    #[error("{0}")]
+    Extract(String),
+    #[error("{0}")]
+    Build(String),
+    #[error("{0}")]
+    Install(String),
+    /// Already reported where it happened (`:: Aborted by user.`)
+    #[error("aborted by user")]
+    Aborted,
+    #[error("{0}")]
+    Config(String),
+    #[error("{0}")]
+    Permission(String),
+    /// Conflicting packages in the plan, or an installed one not removed
+    #[error("{0}")]
+    Conflict(String),
+}
+
+impl YaoError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            YaoError::Config(_) => 3,
+            YaoError::Permission(_) => 4,
+            YaoError::NotFound(_) => 5,
+            YaoError::Network(_) => 6,
+            YaoError::AurRpc(_) => 7,
+            YaoError::Extract(_) => 8,
+            YaoError::Build(_) => 9,
+            YaoError::Install(_) => 10,
+            YaoError::Aborted => 11,
+            YaoError::Conflict(_) => 12,
+        }
+    }
+}
+
+/// The exit code for `err`: that of its outermost [`YaoError`], else 1.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    err.downcast_ref::<YaoError>()
+        .map_or(1, YaoError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, Result, bail};
+
+    fn fails(kind: YaoError) -> Result<()> {
+        bail!(kind)
+    }
+
+    #[test]
+    fn outermost_kind_decides() {
+        let err = fails(YaoError::NotFound("foo not found in AUR".into())).unwrap_err();
+        assert_eq!(exit_code(&err), 5);
+        assert_eq!(err.to_string(), "foo not found in AUR");
+
+        let err = fails(YaoError::Network("connection refused".into()))
+            .context("cannot resolve dependency foo of bar")
+            .unwrap_err();
+        assert_eq!(exit_code(&err), 6);
+
+        let err = fails(YaoError::Network("connection refused".into()))
+            .context(YaoError::Build("building foo failed".into()))
+            .unwrap_err();
+        assert_eq!(exit_code(&err), 9);
+
+        assert_eq!(exit_code(&anyhow::anyhow!("anything else")), 1);
+        assert_eq!(exit_code(&YaoError::Aborted.into()), 11);
+        assert_eq!(exit_code(&YaoError::Conflict("a and b".into()).into()), 12);
+    }
+}
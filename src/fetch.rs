@@ -0,0 +1,368 @@
+//! Fetching AUR build trees: cgit snapshot tarballs or persistent git clones.
+//!
+//! The snapshot backend downloads `{name}.tar.gz` into the snapshot cache and
+//! revalidates it against the RPC. The git backend keeps one clone per
+//! package under the git cache, fast-forwards it on every run, and reports
+//! the commit that is about to be built.
+
+use anyhow::{Context, Result, bail};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+use which::which;
+
+use crate::aur::{AurClient, AurSource};
+use crate::config::Config;
+use crate::error::YaoError;
+use crate::ui::pretty_cmd;
+
+/// Where PKGBUILD trees come from (`fetch_backend`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FetchBackend {
+    Snapshot,
+    Git,
+}
+
+/// A PKGBUILD tree extracted into a work dir.
+pub struct Fetched {
+    /// `{dest}/{pkgbase}`
+    pub dir: PathBuf,
+    /// Commit the tree was taken from (git backend only)
+    pub commit: Option<String>,
+}
+
+/// Fetch the build tree of the pkgbase that builds `name` into
+/// `dest/{pkgbase}` using the configured backend. Snapshots and clones are
+/// cached per pkgbase, so split packages share them.
+pub fn fetch(aur: &AurClient, cfg: &Config, name: &str, dest: &Path) -> Result<Fetched> {
+    let base = aur.pkgbase(name)?;
+    match cfg.fetch_backend {
+        FetchBackend::Snapshot => {
+            let tgz = download_snapshot(aur, cfg, name, &base)?;
+            extract_tgz(&cfg.bsdtar, &tgz, dest)?;
+            Ok(Fetched {
+                dir: dest.join(&base),
+                commit: None,
+            })
+        }
+        FetchBackend::Git => {
+            let url = format!("{}/{base}.git", cfg.git_url());
+            let clone = cfg.git_cache.join(&base);
+            let commit = git_sync(cfg, &url, &clone)?;
+            git_export(cfg, &clone, &dest.join(&base))
+                .with_context(|| YaoError::Extract(format!("cannot export {base}")))?;
+            Ok(Fetched {
+                dir: dest.join(&base),
+                commit: Some(commit),
+            })
+        }
+    }
+}
+
+/* ---------------------- Git backend ---------------------- */
+
+/// Clone `url` into `clone` (first run) or fast-forward it; returns HEAD.
+/// A failed git run is a [`YaoError::Network`], an empty repository a
+/// [`YaoError::NotFound`].
+fn git_sync(cfg: &Config, url: &str, clone: &Path) -> Result<String> {
+    let network = || YaoError::Network(format!("git fetch of {url} failed"));
+    if clone.join(".git").is_dir() {
+        run_git(
+            Command::new(&cfg.git)
+                .arg("-C")
+                .arg(clone)
+                .args(["fetch", "--quiet", "origin"]),
+            cfg.verbose,
+        )
+        .with_context(network)?;
+        run_git(
+            Command::new(&cfg.git).arg("-C").arg(clone).args([
+                "merge",
+                "--ff-only",
+                "--quiet",
+                "@{upstream}",
+            ]),
+            cfg.verbose,
+        )
+        .with_context(network)?;
+    } else {
+        if let Some(parent) = clone.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        run_git(
+            Command::new(&cfg.git)
+                .args(["clone", "--quiet", url])
+                .arg(clone),
+            cfg.verbose,
+        )
+        .with_context(network)?;
+    }
+
+    // The AUR serves an empty repository for any name it does not know.
+    // Drop the clone: it has no upstream to fast-forward next time.
+    let out = Command::new(&cfg.git)
+        .arg("-C")
+        .arg(clone)
+        .args(["rev-parse", "--verify", "--quiet", "HEAD"])
+        .output()?;
+    if !out.status.success() {
+        fs::remove_dir_all(clone)?;
+        bail!(YaoError::NotFound(format!(
+            "{url} is an empty repository (package does not exist?)"
+        )));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// Copy the checked-out tree (without `.git`) to `dest`.
+fn git_export(cfg: &Config, clone: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest)?;
+    let mut archive = Command::new(&cfg.git)
+        .arg("-C")
+        .arg(clone)
+        .args(["archive", "--format=tar", "HEAD"])
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdout = archive.stdout.take().expect("piped stdout");
+    let status = Command::new(which(&cfg.bsdtar)?)
+        .arg("-xf")
+        .arg("-")
+        .arg("-C")
+        .arg(dest)
+        .stdin(stdout)
+        .status()?;
+    if !archive.wait()?.success() || !status.success() {
+        bail!("failed to export {} to {}", clone.display(), dest.display());
+    }
+    Ok(())
+}
+
+fn run_git(cmd: &mut Command, verbose: bool) -> Result<()> {
+    if verbose {
+        eprintln!("$ {}", pretty_cmd(cmd));
+    }
+    let status = cmd.status()?;
+    if !status.success() {
+        bail!("git failed with status {status}");
+    }
+    Ok(())
+}
+
+/* ---------------------- Snapshot backend ---------------------- */
+
+/// Cache metadata stored next to each snapshot as `{name}.meta.json`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SnapshotMeta {
+    /// AUR Version / LastModified at download time
+    version: String,
+    last_modified: i64,
+    /// HTTP validators from the snapshot response
+    etag: Option<String>,
+    http_last_modified: Option<String>,
+    /// Unix time of the download
+    downloaded: u64,
+}
+
+fn read_snapshot_meta(path: &Path) -> Option<SnapshotMeta> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+fn write_snapshot_meta(path: &Path, meta: &SnapshotMeta) -> Result<()> {
+    fs::write(path, serde_json::to_vec_pretty(meta)?)?;
+    Ok(())
+}
+
+/// The cached snapshot of `base`, re-downloaded when the AUR version of
+/// `name` (any package of `base`) moved, the server says it changed, or
+/// with `--refresh`.
+fn download_snapshot(aur: &AurClient, cfg: &Config, name: &str, base: &str) -> Result<PathBuf> {
+    let url = format!("{}/cgit/aur.git/snapshot/{base}.tar.gz", cfg.aur_url);
+    let out = cfg.snapshot_cache.join(format!("{base}.tar.gz"));
+    let meta_path = cfg.snapshot_cache.join(format!("{base}.meta.json"));
+
+    let remote = aur.info(name)?;
+    let cached = if out.exists() && !cfg.refresh {
+        read_snapshot_meta(&meta_path)
+    } else {
+        None
+    };
+
+    // 1) RPC revalidation: same Version + LastModified → cache is current
+    if let (Some(meta), Some(pkg)) = (&cached, &remote)
+        && meta.version == pkg.version
+        && meta.last_modified == pkg.last_modified
+    {
+        if cfg.verbose {
+            eprintln!(
+                "==> Using cached snapshot {} ({})",
+                out.display(),
+                meta.version
+            );
+        }
+        return Ok(out);
+    }
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(ProgressStyle::with_template("{spinner} downloading {msg}")?.tick_chars("/|\\- "));
+    pb.set_message(base.to_string());
+    pb.enable_steady_tick(std::time::Duration::from_millis(80));
+
+    // 2) Conditional request with the stored HTTP validators
+    let mut req = aur.http().get(&url);
+    if let Some(meta) = &cached {
+        if let Some(etag) = &meta.etag {
+            req = req.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(lm) = &meta.http_last_modified {
+            req = req.header(reqwest::header::IF_MODIFIED_SINCE, lm);
+        }
+    }
+    let mut resp = req
+        .send()
+        .with_context(|| YaoError::Network(format!("download failed for {base}")))?;
+
+    let header = |name: reqwest::header::HeaderName, resp: &reqwest::blocking::Response| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let mut meta = SnapshotMeta {
+        version: remote
+            .as_ref()
+            .map(|p| p.version.clone())
+            .unwrap_or_default(),
+        last_modified: remote.as_ref().map_or(0, |p| p.last_modified),
+        etag: header(reqwest::header::ETAG, &resp),
+        http_last_modified: header(reqwest::header::LAST_MODIFIED, &resp),
+        downloaded: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    };
+
+    if resp.status() == reqwest::StatusCode::NOT_MODIFIED
+        && let Some(old) = cached
+    {
+        pb.finish_and_clear();
+        if cfg.verbose {
+            eprintln!("==> Snapshot for {base} not modified, keeping cache");
+        }
+        meta.etag = meta.etag.or(old.etag);
+        meta.http_last_modified = meta.http_last_modified.or(old.http_last_modified);
+        meta.downloaded = old.downloaded;
+        write_snapshot_meta(&meta_path, &meta)?;
+        return Ok(out);
+    }
+    if !resp.status().is_success() {
+        pb.finish_and_clear();
+        bail!(YaoError::Network(format!(
+            "download failed for {base}: {}",
+            resp.status()
+        )));
+    }
+    let mut tmp = tempfile::NamedTempFile::new_in(&cfg.snapshot_cache)?;
+    io::copy(&mut resp, &mut tmp)
+        .with_context(|| YaoError::Network(format!("download failed for {base}")))?;
+    tmp.persist(&out)?;
+    write_snapshot_meta(&meta_path, &meta)?;
+    pb.finish_and_clear();
+    Ok(out)
+}
+
+fn extract_tgz(bsdtar: &str, tgz_path: &Path, dest_dir: &Path) -> Result<()> {
+    let status = Command::new(which(bsdtar)?)
+        .arg("-xzf")
+        .arg(tgz_path)
+        .arg("-C")
+        .arg(dest_dir)
+        .status()?;
+    if !status.success() {
+        bail!(YaoError::Extract(format!(
+            "bsdtar failed to extract {}",
+            tgz_path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let ok = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .expect("git is needed for the git backend tests")
+            .success();
+        assert!(ok, "git {args:?}");
+    }
+
+    #[test]
+    fn git_backend_clones_and_fast_forwards_file_repo() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let bare = tmp.path().join("foo.git");
+        let work = tmp.path().join("work");
+        fs::create_dir_all(&bare).unwrap();
+        git(&bare, &["init", "--quiet", "--bare"]);
+        git(
+            tmp.path(),
+            &["clone", "--quiet", bare.to_str().unwrap(), "work"],
+        );
+
+        fs::write(work.join("PKGBUILD"), "pkgver=1\n").unwrap();
+        git(&work, &["add", "PKGBUILD"]);
+        git(&work, &["commit", "--quiet", "-m", "1"]);
+        git(&work, &["push", "--quiet", "origin", "HEAD"]);
+
+        let url = format!("file://{}", bare.display());
+        let clone = tmp.path().join("cache/foo");
+        let cfg = Config::default();
+        let first = git_sync(&cfg, &url, &clone).unwrap();
+
+        fs::write(work.join("PKGBUILD"), "pkgver=2\n").unwrap();
+        git(&work, &["commit", "--quiet", "-am", "2"]);
+        git(&work, &["push", "--quiet", "origin", "HEAD"]);
+
+        let second = git_sync(&cfg, &url, &clone).unwrap();
+        assert_ne!(first, second);
+
+        let out = tmp.path().join("out/foo");
+        git_export(&cfg, &clone, &out).unwrap();
+        assert_eq!(
+            fs::read_to_string(out.join("PKGBUILD")).unwrap(),
+            "pkgver=2\n"
+        );
+        assert!(!out.join(".git").exists());
+    }
+
+    #[test]
+    fn git_backend_rejects_empty_repo() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let bare = tmp.path().join("nope.git");
+        fs::create_dir_all(&bare).unwrap();
+        git(&bare, &["init", "--quiet", "--bare"]);
+        let url = format!("file://{}", bare.display());
+        let cfg = Config::default();
+        let clone = tmp.path().join("cache/nope");
+        // still "not found" the second time, not a failed fast-forward
+        for _ in 0..2 {
+            let err = git_sync(&cfg, &url, &clone).unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(YaoError::NotFound(_))),
+                "{err:#}"
+            );
+        }
+        assert!(!clone.exists());
+    }
+}
@@ -0,0 +1,44 @@
+//! yaorust: a fast, minimal yaourt-style AUR + repo helper.
+//!
+//! The `yao` binary is a thin command-line front-end over [`commands`]. The
+//! pieces it is made of can be used on their own:
+//!
+//! - [`aur`]: RPC records and the [`aur::AurSource`] interface, implemented
+//!   over HTTP by [`aur::AurClient`]
+//! - [`pacman`]: the [`pacman::PackageDb`] interface, implemented by reading
+//!   pacman's databases ([`pacman::db::PacmanDb`]), and pacman transactions
+//! - [`build`]: the [`build::Builder`] interface, implemented with makepkg
+//!   ([`build::MakepkgBuilder`]), and the root-safe ways of running it
+//! - [`fetch`]: snapshot and git build trees
+//! - [`deps`], [`conflicts`], [`providers`], [`targets`]: turning targets
+//!   into a build plan
+//! - [`ui`]: prompts, search menu, `-Si` output and PKGBUILD review
+//! - [`config`]: layered configuration
+//! - [`error`]: the failure kinds behind `yao`'s exit codes
+
+pub mod aur;
+pub mod build;
+pub mod commands;
+pub mod config;
+pub mod conflicts;
+pub mod deps;
+pub mod error;
+pub mod fetch;
+pub mod pacman;
+pub mod providers;
+pub mod targets;
+pub mod ui;
+
+pub fn is_root() -> bool {
+    nix_like_geteuid() == 0
+}
+
+#[cfg(target_family = "unix")]
+pub fn nix_like_geteuid() -> u32 {
+    unsafe { libc::geteuid() }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn nix_like_geteuid() -> u32 {
+    1
+}
@@ -0,0 +1,437 @@
+//! Read-only view of the pacman databases.
+//!
+//! Asking pacman about one package at a time (`-Qi`, `-Si`, `-T`) costs a
+//! process per package, which adds up quickly over the whole dependency tree
+//! of an upgrade. Instead we read `{DBPath}/local/*/desc` and the sync
+//! databases `{DBPath}/sync/{repo}.db` (tar, gzip or zstd compressed) once
+//! into memory.
+//!
+//! The index goes stale as soon as a transaction runs, so checks made after
+//! pacman has installed something read the databases again.
+
+use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::PackageDb;
+use crate::config::Config;
+
+/// One package entry of the local or a sync database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbPkg {
+    pub name: String,
+    pub version: String,
+    pub provides: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub groups: Vec<String>,
+    /// Installed as a dependency (`%REASON%` 1); always false in sync DBs
+    pub as_dep: bool,
+}
+
+/// Packages of one sync repository.
+#[derive(Debug, Clone)]
+pub struct SyncRepo {
+    pub name: String,
+    pub pkgs: Vec<DbPkg>,
+}
+
+#[derive(Debug, Default)]
+pub struct PacmanDb {
+    local: Vec<DbPkg>,
+    /// name -> index into `local`
+    installed: HashMap<String, usize>,
+    /// In pacman.conf order; the first repo carrying a name wins, like `pacman -S`
+    sync: Vec<SyncRepo>,
+    /// name -> (repo, package) index into `sync`, first repo only
+    sync_names: HashMap<String, (usize, usize)>,
+    /// group -> members as indexes into `sync`, in repo order, one per name
+    groups: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl PacmanDb {
+    /// Load the databases below `cfg.dbpath`, repos in pacman.conf order.
+    pub fn open(cfg: &Config) -> Result<Self> {
+        let repos = repo_list(&cfg.pacman_conf, &cfg.dbpath)?;
+        if cfg.verbose {
+            eprintln!(
+                "==> reading pacman databases in {} (repos: {})",
+                cfg.dbpath.display(),
+                repos.join(" ")
+            );
+        }
+        Self::load(&cfg.dbpath, &repos, &cfg.bsdtar)
+    }
+
+    /// Load `{dbpath}/local` and `{dbpath}/sync/{repo}.db` for each of `repos`.
+    /// A repo whose database was never downloaded is skipped; `bsdtar` reads
+    /// compressions we do not handle ourselves.
+    pub fn load(dbpath: &Path, repos: &[String], bsdtar: &str) -> Result<Self> {
+        let mut db = PacmanDb::default();
+
+        let local = dbpath.join("local");
+        let mut dirs: Vec<PathBuf> = match fs::read_dir(&local) {
+            Ok(rd) => rd
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.is_dir())
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).context(format!("cannot read {}", local.display())),
+        };
+        dirs.sort();
+        for dir in dirs {
+            let Ok(text) = fs::read_to_string(dir.join("desc")) else {
+                continue;
+            };
+            let mut pkg = DbPkg::default();
+            parse_desc(&text, &mut pkg);
+            if !pkg.name.is_empty() {
+                db.installed.insert(pkg.name.clone(), db.local.len());
+                db.local.push(pkg);
+            }
+        }
+
+        for repo in repos {
+            let path = dbpath.join("sync").join(format!("{repo}.db"));
+            let data = match fs::read(&path) {
+                Ok(d) => d,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).context(format!("cannot read {}", path.display())),
+            };
+            let pkgs = read_sync_db(&path, &data, bsdtar)
+                .with_context(|| format!("cannot read sync database {}", path.display()))?;
+            db.sync.push(SyncRepo {
+                name: repo.clone(),
+                pkgs,
+            });
+        }
+        db.index_sync();
+        Ok(db)
+    }
+
+    /// Build the name and group lookups over `sync`.
+    fn index_sync(&mut self) {
+        for (r, repo) in self.sync.iter().enumerate() {
+            for (i, p) in repo.pkgs.iter().enumerate() {
+                self.sync_names.entry(p.name.clone()).or_insert((r, i));
+                for g in &p.groups {
+                    let members = self.groups.entry(g.clone()).or_default();
+                    if !members
+                        .iter()
+                        .any(|&(mr, mi)| self.sync[mr].pkgs[mi].name == p.name)
+                    {
+                        members.push((r, i));
+                    }
+                }
+            }
+        }
+    }
+
+    fn sync_entry(&self, (r, i): (usize, usize)) -> (&str, &DbPkg) {
+        let repo = &self.sync[r];
+        (repo.name.as_str(), &repo.pkgs[i])
+    }
+}
+
+impl PackageDb for PacmanDb {
+    fn local(&self, name: &str) -> Option<&DbPkg> {
+        self.installed.get(name).map(|&i| &self.local[i])
+    }
+
+    fn local_pkgs(&self) -> &[DbPkg] {
+        &self.local
+    }
+
+    fn sync_pkgs(&self) -> Box<dyn Iterator<Item = (&str, &DbPkg)> + '_> {
+        Box::new(
+            self.sync
+                .iter()
+                .flat_map(|r| r.pkgs.iter().map(move |p| (r.name.as_str(), p))),
+        )
+    }
+
+    fn sync(&self, name: &str) -> Option<(&str, &DbPkg)> {
+        self.sync_names.get(name).map(|&at| self.sync_entry(at))
+    }
+
+    fn foreign(&self) -> Vec<&DbPkg> {
+        self.local
+            .iter()
+            .filter(|p| !self.sync_names.contains_key(&p.name))
+            .collect()
+    }
+
+    fn group_members(&self, name: &str) -> Vec<(&str, &DbPkg)> {
+        self.groups
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|&at| self.sync_entry(at))
+            .collect()
+    }
+}
+
+/// Sync repos in pacman.conf order, as `pacman_conf --repo-list` prints
+/// them. Without pacman-conf (or for a DBPath pacman.conf knows nothing
+/// about) every `sync/*.db` is used, sorted.
+pub fn repo_list(pacman_conf: &str, dbpath: &Path) -> Result<Vec<String>> {
+    let sync = dbpath.join("sync");
+    if let Ok(repos) = run_pacman_conf(pacman_conf, &["--repo-list"]) {
+        let repos: Vec<String> = repos
+            .into_iter()
+            .filter(|r| sync.join(format!("{r}.db")).is_file())
+            .collect();
+        if !repos.is_empty() {
+            return Ok(repos);
+        }
+    }
+    let mut repos: Vec<String> = match fs::read_dir(&sync) {
+        Ok(rd) => rd
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                name.strip_suffix(".db").map(str::to_string)
+            })
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e).context(format!("cannot read {}", sync.display())),
+    };
+    repos.sort();
+    Ok(repos)
+}
+
+/// `pacman_conf DBPath`, if pacman-conf is around.
+pub fn default_dbpath(pacman_conf: &str) -> Option<PathBuf> {
+    run_pacman_conf(pacman_conf, &["DBPath"])
+        .ok()?
+        .into_iter()
+        .next()
+        .map(PathBuf::from)
+}
+
+fn run_pacman_conf(pacman_conf: &str, args: &[&str]) -> Result<Vec<String>> {
+    let out = Command::new(pacman_conf)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()?;
+    if !out.status.success() {
+        bail!("pacman-conf {} failed", args.join(" "));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Parse one sync database archive. Each package is a `{name}-{ver}/`
+/// directory with a `desc` file (and a separate `depends` in old databases).
+fn read_sync_db(path: &Path, data: &[u8], bsdtar: &str) -> Result<Vec<DbPkg>> {
+    let tar: Box<dyn Read + '_> = match data {
+        [0x1f, 0x8b, ..] => Box::new(GzDecoder::new(data)),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Box::new(
+            ruzstd::decoding::StreamingDecoder::new(data)
+                .map_err(|e| anyhow::anyhow!("zstd: {e}"))?,
+        ),
+        // plain tar ("ustar" at offset 257); anything else (xz, bzip2, ...)
+        // goes through bsdtar
+        _ if data.get(257..262) == Some(b"ustar") => Box::new(data),
+        _ => Box::new(io::Cursor::new(bsdtar_to_tar(bsdtar, path)?)),
+    };
+
+    let mut pkgs: BTreeMap<String, DbPkg> = BTreeMap::new();
+    let mut archive = tar::Archive::new(tar);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let (Some(dir), Some(file)) = (path.parent(), path.file_name()) else {
+            continue;
+        };
+        if file != "desc" && file != "depends" {
+            continue;
+        }
+        let mut text = String::new();
+        entry.read_to_string(&mut text)?;
+        let pkg = pkgs.entry(dir.to_string_lossy().into_owned()).or_default();
+        parse_desc(&text, pkg);
+    }
+    Ok(pkgs.into_values().filter(|p| !p.name.is_empty()).collect())
+}
+
+/// Recompress an archive libarchive understands into a plain tar.
+fn bsdtar_to_tar(bsdtar: &str, path: &Path) -> Result<Vec<u8>> {
+    let mut src = std::ffi::OsString::from("@");
+    src.push(path);
+    let out = Command::new(bsdtar)
+        .arg("-cf")
+        .arg("-")
+        .arg(src)
+        .stderr(Stdio::null())
+        .output()
+        .context("unknown database compression and bsdtar is not available")?;
+    if !out.status.success() {
+        bail!("unknown database format");
+    }
+    Ok(out.stdout)
+}
+
+/// Parse a `desc` (or `depends`) file into `pkg`: `%SECTION%` headers, one
+/// value per line, sections separated by blank lines.
+fn parse_desc(text: &str, pkg: &mut DbPkg) {
+    let mut section = "";
+    for line in text.lines() {
+        if line.starts_with('%') && line.ends_with('%') {
+            section = line;
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        match section {
+            "%NAME%" => pkg.name = line.to_string(),
+            "%VERSION%" => pkg.version = line.to_string(),
+            "%PROVIDES%" => pkg.provides.push(line.to_string()),
+            "%CONFLICTS%" => pkg.conflicts.push(line.to_string()),
+            "%GROUPS%" => pkg.groups.push(line.to_string()),
+            "%REASON%" => pkg.as_dep = line == "1",
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn desc(name: &str, version: &str, extra: &str) -> String {
+        format!("%NAME%\n{name}\n\n%VERSION%\n{version}\n\n{extra}")
+    }
+
+    fn tar_of(files: &[(&str, String)]) -> Vec<u8> {
+        let mut b = tar::Builder::new(Vec::new());
+        for (path, text) in files {
+            let mut h = tar::Header::new_gnu();
+            h.set_size(text.len() as u64);
+            h.set_mode(0o644);
+            h.set_cksum();
+            b.append_data(&mut h, path, text.as_bytes()).unwrap();
+        }
+        b.into_inner().unwrap()
+    }
+
+    #[test]
+    fn reads_fixture_databases() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path();
+        for (dir, text) in [
+            ("bash-5.2-1", desc("bash", "5.2-1", "%PROVIDES%\nsh\n\n")),
+            ("yay-12.0-1", desc("yay", "12.0-1", "%REASON%\n1\n\n")),
+            (
+                "foo-git-1-1",
+                desc(
+                    "foo-git",
+                    "1-1",
+                    "%CONFLICTS%\nfoo\n\n%PROVIDES%\nfoo=1\n\n",
+                ),
+            ),
+        ] {
+            fs::create_dir_all(root.join("local").join(dir)).unwrap();
+            fs::write(root.join("local").join(dir).join("desc"), text).unwrap();
+        }
+
+        fs::create_dir_all(root.join("sync")).unwrap();
+        let core = tar_of(&[
+            (
+                "bash-5.2-2/desc",
+                desc("bash", "5.2-2", "%PROVIDES%\nsh\n\n"),
+            ),
+            (
+                "base-devel-1-1/desc",
+                desc("base-devel", "1-1", "%GROUPS%\nbase-devel\n\n"),
+            ),
+        ]);
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gz.write_all(&core).unwrap();
+        fs::write(root.join("sync/core.db"), gz.finish().unwrap()).unwrap();
+
+        // old-style database: provides in a separate `depends` file
+        let extra = tar_of(&[
+            ("bash-9-1/desc", desc("bash", "9-1", "")),
+            ("jre-1-1/desc", desc("jre", "1-1", "")),
+            (
+                "base-devel-2-1/desc",
+                desc("base-devel", "2-1", "%GROUPS%\nbase-devel\n\n"),
+            ),
+            (
+                "gcc-14-1/desc",
+                desc("gcc", "14-1", "%GROUPS%\nbase-devel\n\n"),
+            ),
+            (
+                "jre-1-1/depends",
+                "%PROVIDES%\njava-runtime=21\n\n".to_string(),
+            ),
+        ]);
+        let zst = ruzstd::encoding::compress_to_vec(
+            &extra[..],
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        fs::write(root.join("sync/extra.db"), zst).unwrap();
+        fs::write(
+            root.join("sync/plain.db"),
+            tar_of(&[("x-1-1/desc", desc("x", "1-1", ""))]),
+        )
+        .unwrap();
+
+        // no pacman-conf: every sync/*.db, sorted
+        let repos = repo_list("/nonexistent/pacman-conf", root).unwrap();
+        assert_eq!(repos, ["core", "extra", "plain"]);
+        let db = PacmanDb::load(root, &repos, "bsdtar").unwrap();
+
+        assert!(db.is_installed("yay"));
+        assert!(db.local("yay").unwrap().as_dep);
+        assert!(!db.local("bash").unwrap().as_dep);
+        assert_eq!(db.local("foo-git").unwrap().conflicts, ["foo"]);
+        assert!(db.dep_satisfied("sh"));
+        assert!(db.dep_satisfied("foo>=1"));
+        assert!(!db.dep_satisfied("foo>1"));
+        assert!(!db.dep_satisfied("zsh"));
+
+        let (repo, bash) = db.sync("bash").unwrap();
+        assert_eq!((repo, bash.version.as_str()), ("core", "5.2-2"));
+        assert_eq!(db.sync("jre").unwrap().1.provides, ["java-runtime=21"]);
+        assert_eq!(db.sync("x").unwrap().0, "plain");
+        // a member carried by two repos is taken from the first
+        let members: Vec<(&str, &str)> = db
+            .group_members("base-devel")
+            .iter()
+            .map(|(repo, p)| (*repo, p.name.as_str()))
+            .collect();
+        assert_eq!(members, [("core", "base-devel"), ("extra", "gcc")]);
+        assert!(db.group_members("bash").is_empty());
+
+        let foreign: Vec<&str> = db.foreign().iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(foreign, ["foo-git", "yay"]);
+    }
+
+    #[test]
+    fn repo_list_follows_pacman_conf() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path();
+        fs::create_dir_all(root.join("sync")).unwrap();
+        for repo in ["core", "extra", "local"] {
+            fs::write(root.join(format!("sync/{repo}.db")), "").unwrap();
+        }
+        let conf = root.join("pacman-conf");
+        fs::write(&conf, "#!/bin/sh\nprintf 'extra\\ncore\\nmultilib\\n'\n").unwrap();
+        fs::set_permissions(&conf, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // pacman.conf order, repos without a database left out
+        let repos = repo_list(conf.to_str().unwrap(), root).unwrap();
+        assert_eq!(repos, ["extra", "core"]);
+    }
+}
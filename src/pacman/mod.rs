@@ -0,0 +1,121 @@
+//! pacman: the package database view and the transactions we run through
+//! pacman itself.
+//!
+//! Lookups go through [`PackageDb`], which [`db::PacmanDb`] implements by
+//! reading the databases directly (re-read after a transaction when its
+//! outcome matters). Everything that changes the system spawns `pacman`,
+//! with `sudo` in front when we are not root.
+
+pub mod db;
+pub mod vercmp;
+
+use anyhow::Result;
+use std::process::Command;
+
+use crate::config::Config;
+use crate::is_root;
+use crate::ui::run_pacman;
+use db::DbPkg;
+use vercmp::Dep;
+
+/// Read access to the local and sync package databases.
+///
+/// Only the three listings are required; the queries built on them mirror
+/// `pacman -Q`/`-S`/`-T` and may be overridden by faster implementations.
+pub trait PackageDb {
+    /// The installed package called `name`.
+    fn local(&self, name: &str) -> Option<&DbPkg>;
+
+    /// Every installed package.
+    fn local_pkgs(&self) -> &[DbPkg];
+
+    /// Every sync package with its repo, in repo order.
+    fn sync_pkgs(&self) -> Box<dyn Iterator<Item = (&str, &DbPkg)> + '_>;
+
+    fn is_installed(&self, name: &str) -> bool {
+        self.local(name).is_some()
+    }
+
+    /// Like `pacman -T dep`: satisfied by an installed package, by name or provides.
+    fn dep_satisfied(&self, dep: &str) -> bool {
+        let dep = Dep::parse(dep);
+        self.local_pkgs()
+            .iter()
+            .any(|p| dep.satisfied_by_pkg(&p.name, &p.version, &p.provides))
+    }
+
+    /// Installed packages in no sync repo (`pacman -Qm`).
+    fn foreign(&self) -> Vec<&DbPkg> {
+        self.local_pkgs()
+            .iter()
+            .filter(|p| self.sync(&p.name).is_none())
+            .collect()
+    }
+
+    /// The sync package called `name` and its repo, first repo first.
+    fn sync(&self, name: &str) -> Option<(&str, &DbPkg)> {
+        self.sync_pkgs().find(|(_, p)| p.name == name)
+    }
+
+    /// Members of the group `name` with their repo, in repo order. A name
+    /// carried by several repos is only taken from the first, like pacman.
+    fn group_members(&self, name: &str) -> Vec<(&str, &DbPkg)> {
+        let mut out: Vec<(&str, &DbPkg)> = Vec::new();
+        for (repo, p) in self.sync_pkgs() {
+            if p.groups.iter().any(|g| g == name) && !out.iter().any(|(_, q)| q.name == p.name) {
+                out.push((repo, p));
+            }
+        }
+        out
+    }
+}
+
+/// pacman for a transaction, with --noconfirm passed on.
+pub fn command(cfg: &Config) -> Command {
+    let mut cmd = Command::new(&cfg.pacman);
+    if cfg.noconfirm {
+        cmd.arg("--noconfirm");
+    }
+    cmd
+}
+
+pub fn with_sudo(cfg: &Config, cmd: Command) -> Command {
+    let prog = cmd.get_program().to_os_string();
+    let args: Vec<_> = cmd.get_args().map(|s| s.to_os_string()).collect();
+
+    let mut sc = Command::new(&cfg.sudo);
+    sc.arg(prog);
+    sc.args(args);
+    sc
+}
+
+/// Call pacman -S for repo packages, let pacman show all info + its own [Y/n] prompt.
+pub fn install_repo(cfg: &Config, pkgs: &[String]) -> Result<()> {
+    let mut cmd = command(cfg);
+    cmd.arg("-S");
+    // without --needed, behave like plain pacman (allow reinstall)
+    if cfg.needed {
+        cmd.arg("--needed");
+    }
+    cmd.args(pkgs);
+
+    if !is_root() {
+        cmd = with_sudo(cfg, cmd);
+    }
+
+    // "n" at pacman's prompt is an install failure too (exit code 1 either way)
+    run_pacman(&mut cmd, cfg.verbose)
+}
+
+/// pacman -Su (or -Syu), with pacman's own output and prompt.
+pub fn sysupgrade(cfg: &Config, refresh: bool) -> Result<()> {
+    let mut cmd = command(cfg);
+    cmd.arg(if refresh { "-Syu" } else { "-Su" });
+
+    if !is_root() {
+        cmd = with_sudo(cfg, cmd);
+    }
+
+    eprintln!("==> [repo] upgrading via pacman");
+    run_pacman(&mut cmd, cfg.verbose)
+}
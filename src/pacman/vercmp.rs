@@ -0,0 +1,338 @@
+//! pacman-compatible version comparison.
+//!
+//! A port of libalpm's `alpm_pkg_vercmp` / `rpmvercmp`, so `epoch:pkgver-pkgrel`
+//! strings order exactly like `vercmp(8)`, plus evaluation of versioned
+//! dependency strings such as `foo>=1.2-3`.
+
+use std::cmp::Ordering;
+
+/// Compare two full versions (`[epoch:]pkgver[-pkgrel]`) like `alpm_pkg_vercmp`.
+///
+/// The pkgrel is only compared when both sides have one, so `1.5` == `1.5-1`.
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (e1, v1, r1) = parse_evr(a);
+    let (e2, v2, r2) = parse_evr(b);
+
+    rpmvercmp(e1, e2)
+        .then_with(|| rpmvercmp(v1, v2))
+        .then_with(|| match (r1, r2) {
+            (Some(r1), Some(r2)) => rpmvercmp(r1, r2),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Split `[epoch:]version[-release]` the way libalpm's `parseEVR` does.
+fn parse_evr(evr: &str) -> (&str, &str, Option<&str>) {
+    let digits = evr.bytes().take_while(u8::is_ascii_digit).count();
+    let (epoch, rest) = if evr.as_bytes().get(digits) == Some(&b':') {
+        let epoch = &evr[..digits];
+        (
+            if epoch.is_empty() { "0" } else { epoch },
+            &evr[digits + 1..],
+        )
+    } else {
+        ("0", evr)
+    };
+    match rest.rfind('-') {
+        Some(i) => (epoch, &rest[..i], Some(&rest[i + 1..])),
+        None => (epoch, rest, None),
+    }
+}
+
+/// Segment-wise comparison of a single version component (libalpm `rpmvercmp`).
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let a = a.as_bytes();
+    let b = b.as_bytes();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        // skip separators, remembering how many there were
+        let (sep1, sep2) = (i, j);
+        while i < a.len() && !a[i].is_ascii_alphanumeric() {
+            i += 1;
+        }
+        while j < b.len() && !b[j].is_ascii_alphanumeric() {
+            j += 1;
+        }
+        if i >= a.len() || j >= b.len() {
+            break;
+        }
+        // different separator lengths decide on their own
+        if i - sep1 != j - sep2 {
+            return (i - sep1).cmp(&(j - sep2));
+        }
+
+        // grab the next segment of the same class from both
+        let isnum = a[i].is_ascii_digit();
+        let class = |c: &u8| {
+            if isnum {
+                c.is_ascii_digit()
+            } else {
+                c.is_ascii_alphabetic()
+            }
+        };
+        let end1 = i + a[i..].iter().take_while(|c| class(c)).count();
+        let end2 = j + b[j..].iter().take_while(|c| class(c)).count();
+        let mut seg1 = &a[i..end1];
+        let mut seg2 = &b[j..end2];
+
+        // segment types differ: numeric is newer than alpha
+        if seg2.is_empty() {
+            return if isnum {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        if isnum {
+            while seg1.first() == Some(&b'0') {
+                seg1 = &seg1[1..];
+            }
+            while seg2.first() == Some(&b'0') {
+                seg2 = &seg2[1..];
+            }
+            match seg1.len().cmp(&seg2.len()) {
+                Ordering::Equal => {}
+                other => return other,
+            }
+        }
+        match seg1.cmp(seg2) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+
+        i = end1;
+        j = end2;
+    }
+
+    let rest1 = &a[i.min(a.len())..];
+    let rest2 = &b[j.min(b.len())..];
+    if rest1.is_empty() && rest2.is_empty() {
+        return Ordering::Equal;
+    }
+    // The final showdown: a remaining alpha string never beats an empty one.
+    // - if a is empty and b is not alpha, b is newer
+    // - if a is alpha, b is newer
+    // - otherwise a is newer
+    let alpha = |s: &[u8]| s.first().is_some_and(u8::is_ascii_alphabetic);
+    if (rest1.is_empty() && !alpha(rest2)) || alpha(rest1) {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+/// Version operator in a dependency string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepOp {
+    /// Unversioned: any version satisfies
+    Any,
+    Eq,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+/// A parsed dependency such as `foo`, `foo>=1.2` or `foo=1:2.0-3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dep {
+    pub name: String,
+    pub op: DepOp,
+    /// Empty for `DepOp::Any`
+    pub version: String,
+}
+
+impl Dep {
+    pub fn parse(s: &str) -> Self {
+        let s = s.trim();
+        let Some(pos) = s.find(['<', '>', '=']) else {
+            return Self {
+                name: s.to_string(),
+                op: DepOp::Any,
+                version: String::new(),
+            };
+        };
+        let (name, rest) = s.split_at(pos);
+        let (op, len) = match rest.as_bytes() {
+            [b'>', b'=', ..] => (DepOp::Ge, 2),
+            [b'<', b'=', ..] => (DepOp::Le, 2),
+            [b'>', ..] => (DepOp::Gt, 1),
+            [b'<', ..] => (DepOp::Lt, 1),
+            _ => (DepOp::Eq, 1),
+        };
+        Self {
+            name: name.to_string(),
+            op,
+            version: rest[len..].to_string(),
+        }
+    }
+
+    /// Whether a package at `version` satisfies the version constraint.
+    pub fn satisfied_by(&self, version: &str) -> bool {
+        let ord = vercmp(version, &self.version);
+        match self.op {
+            DepOp::Any => true,
+            DepOp::Eq => ord.is_eq(),
+            DepOp::Ge => ord.is_ge(),
+            DepOp::Le => ord.is_le(),
+            DepOp::Gt => ord.is_gt(),
+            DepOp::Lt => ord.is_lt(),
+        }
+    }
+
+    /// alpm_depcmp: satisfied by the package itself or one of its provides.
+    /// An unversioned provision only satisfies an unversioned dependency.
+    pub fn satisfied_by_pkg(&self, name: &str, version: &str, provides: &[String]) -> bool {
+        if name == self.name && self.satisfied_by(version) {
+            return true;
+        }
+        provides.iter().map(|p| Dep::parse(p)).any(|p| {
+            p.name == self.name
+                && match (self.op, p.op) {
+                    (DepOp::Any, _) => true,
+                    (_, DepOp::Eq) => self.satisfied_by(&p.version),
+                    _ => false,
+                }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Table from pacman's `test/util/vercmptest.sh`.
+    const CASES: &[(&str, &str, i8)] = &[
+        // all similar length, no pkgrel
+        ("1.5.0", "1.5.0", 0),
+        ("1.5.1", "1.5.0", 1),
+        // mixed length
+        ("1.5.1", "1.5", 1),
+        // with pkgrel, simple
+        ("1.5.0-1", "1.5.0-1", 0),
+        ("1.5.0-1", "1.5.0-2", -1),
+        ("1.5.0-1", "1.5.1-1", -1),
+        ("1.5.0-2", "1.5.1-1", -1),
+        // with pkgrel, mixed lengths
+        ("1.5-1", "1.5.1-1", -1),
+        ("1.5-2", "1.5.1-1", -1),
+        ("1.5-2", "1.5.1-2", -1),
+        // mixed pkgrel inclusion
+        ("1.5", "1.5-1", 0),
+        ("1.5-1", "1.5", 0),
+        ("1.1-1", "1.1", 0),
+        ("1.0-1", "1.1", -1),
+        ("1.1-1", "1.0", 1),
+        // alphanumeric versions
+        ("1.5b-1", "1.5-1", -1),
+        ("1.5b", "1.5", -1),
+        ("1.5b-1", "1.5", -1),
+        ("1.5b", "1.5.1", -1),
+        // from the manpage
+        ("1.0a", "1.0alpha", -1),
+        ("1.0alpha", "1.0b", -1),
+        ("1.0b", "1.0beta", -1),
+        ("1.0beta", "1.0rc", -1),
+        ("1.0rc", "1.0", -1),
+        // alpha-dotted versions
+        ("1.5.a", "1.5", 1),
+        ("1.5.b", "1.5.a", 1),
+        ("1.5.1", "1.5.b", 1),
+        // alpha dots and dashes
+        ("1.5.b-1", "1.5.b", 0),
+        ("1.5-1", "1.5.b", -1),
+        // same/similar content, differing separators
+        ("2.0", "2_0", 0),
+        ("2.0_a", "2_0.a", 0),
+        ("2.0a", "2.0.a", -1),
+        ("2___a", "2_a", 1),
+        // epoch included version comparisons
+        ("0:1.0", "0:1.0", 0),
+        ("0:1.0", "0:1.1", -1),
+        ("1:1.0", "0:1.0", 1),
+        ("1:1.0", "0:1.1", 1),
+        ("1:1.0", "2:1.1", -1),
+        // epoch + sometimes present pkgrel
+        ("1:1.0", "0:1.0-1", 1),
+        ("1:1.0-1", "0:1.1-1", 1),
+        // epoch included on one version
+        ("0:1.0", "1.0", 0),
+        ("0:1.0", "1.1", -1),
+        ("0:1.1", "1.0", 1),
+        ("1:1.0", "1.0", 1),
+        ("1:1.0", "1.1", 1),
+        ("1:1.1", "1.1", 1),
+        // leading zeros and long numbers
+        ("1.01", "1.1", 0),
+        ("1.001", "1.0001", 0),
+        ("20240101", "20231231", 1),
+        ("1.10", "1.9", 1),
+        ("1.0.0", "1.0", 1),
+        ("r123.abcdef", "r99.fedcba", 1),
+    ];
+
+    fn expect(n: i8) -> Ordering {
+        n.cmp(&0)
+    }
+
+    #[test]
+    fn vercmp_matches_pacman_table() {
+        for &(a, b, want) in CASES {
+            assert_eq!(vercmp(a, b), expect(want), "vercmp({a}, {b})");
+            // the comparison must be antisymmetric
+            assert_eq!(vercmp(b, a), expect(-want), "vercmp({b}, {a})");
+        }
+    }
+
+    #[test]
+    fn parse_evr_splits_epoch_and_release() {
+        assert_eq!(parse_evr("1:2.0-3"), ("1", "2.0", Some("3")));
+        assert_eq!(parse_evr("2.0"), ("0", "2.0", None));
+        assert_eq!(parse_evr(":2.0-1"), ("0", "2.0", Some("1")));
+        assert_eq!(parse_evr("1.0-rc1-2"), ("0", "1.0-rc1", Some("2")));
+    }
+
+    #[test]
+    fn dep_parse_and_satisfy() {
+        let d = Dep::parse("foo>=1.2");
+        assert_eq!(d.name, "foo");
+        assert_eq!(d.op, DepOp::Ge);
+        assert!(d.satisfied_by("1.2-1"));
+        assert!(d.satisfied_by("1:0.1-1"));
+        assert!(!d.satisfied_by("1.1.9-4"));
+
+        let d = Dep::parse("bar");
+        assert_eq!(d.op, DepOp::Any);
+        assert!(d.satisfied_by("0.0.1-1"));
+
+        // pkgrel-less constraint matches any pkgrel
+        let d = Dep::parse("baz=2.0");
+        assert!(d.satisfied_by("2.0-7"));
+        assert!(!d.satisfied_by("2.0.1-1"));
+
+        assert!(Dep::parse("q<2").satisfied_by("1.9"));
+        assert!(!Dep::parse("q<2").satisfied_by("2"));
+        assert!(Dep::parse("q<=2").satisfied_by("2"));
+        assert!(Dep::parse("q>1").satisfied_by("1.0.1"));
+        assert!(!Dep::parse("q>1").satisfied_by("1"));
+    }
+
+    #[test]
+    fn dep_satisfied_by_provides() {
+        let provides = ["sh".to_string(), "libfoo.so=2-64".to_string()];
+        assert!(Dep::parse("sh").satisfied_by_pkg("bash", "5.2-1", &provides));
+        assert!(!Dep::parse("sh>=1").satisfied_by_pkg("bash", "5.2-1", &provides));
+        assert!(Dep::parse("libfoo.so>=2").satisfied_by_pkg("foo", "1-1", &provides));
+        assert!(!Dep::parse("libfoo.so>2").satisfied_by_pkg("foo", "1-1", &provides));
+        assert!(Dep::parse("foo<2").satisfied_by_pkg("foo", "1-1", &[]));
+        assert!(!Dep::parse("bar").satisfied_by_pkg("foo", "1-1", &provides));
+    }
+}
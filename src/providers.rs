@@ -0,0 +1,155 @@
+//! Picking a provider for virtual dependencies (`java-runtime`, `sh`, ...).
+//!
+//! Candidates come from the sync databases (every package whose `provides`
+//! names the dependency) and from the AUR (`search by=provides`). A single
+//! candidate is taken as is; otherwise a configured preference wins, and
+//! failing that the user picks from a numbered list laid out like pacman's.
+
+use anyhow::Result;
+use std::io::{self, Write};
+
+use crate::aur::AurSource;
+use crate::config::Config;
+use crate::pacman::PackageDb;
+use crate::pacman::vercmp::Dep;
+
+/// One package that can satisfy a dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provider {
+    pub name: String,
+    pub version: String,
+    /// Sync repo name, or None for the AUR
+    pub repo: Option<String>,
+}
+
+pub struct Providers<'a> {
+    cfg: &'a Config,
+    aur: &'a dyn AurSource,
+    db: &'a dyn PackageDb,
+}
+
+impl<'a> Providers<'a> {
+    pub fn new(cfg: &'a Config, aur: &'a dyn AurSource, db: &'a dyn PackageDb) -> Self {
+        Self { cfg, aur, db }
+    }
+
+    /// Choose a provider for `name`; None if nothing provides it.
+    pub fn choose(&self, name: &str) -> Result<Option<Provider>> {
+        let candidates = self.candidates(name)?;
+        if candidates.len() <= 1 {
+            return Ok(candidates.into_iter().next());
+        }
+        // dep_satisfied normally catches these; a versioned dep may not
+        if let Some(p) = candidates.iter().find(|p| self.db.is_installed(&p.name)) {
+            if self.cfg.verbose {
+                eprintln!("==> {} is installed and provides {name}", p.name);
+            }
+            return Ok(Some(p.clone()));
+        }
+        if let Some(pref) = self.cfg.providers.get(name) {
+            if let Some(p) = candidates.iter().find(|p| &p.name == pref) {
+                if self.cfg.verbose {
+                    eprintln!("==> using preferred provider {} for {name}", p.name);
+                }
+                return Ok(Some(p.clone()));
+            }
+            eprintln!("==> preferred provider {pref} for {name} is not available");
+        }
+        let i = prompt_provider(self.cfg, name, &candidates)?;
+        Ok(candidates.into_iter().nth(i))
+    }
+
+    /// Repo providers first (in repo order), then AUR providers.
+    fn candidates(&self, name: &str) -> Result<Vec<Provider>> {
+        let mut out: Vec<Provider> = self
+            .db
+            .sync_pkgs()
+            .filter(|(_, p)| {
+                p.name == name || p.provides.iter().any(|d| Dep::parse(d).name == name)
+            })
+            .map(|(repo, p)| Provider {
+                name: p.name.clone(),
+                version: p.version.clone(),
+                repo: Some(repo.to_string()),
+            })
+            .collect();
+        for pkg in self.aur.search_provides(name)? {
+            if !out.iter().any(|p| p.name == pkg.name) {
+                out.push(Provider {
+                    name: pkg.name,
+                    version: pkg.version,
+                    repo: None,
+                });
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// pacman's provider menu; returns the index into `candidates`.
+fn prompt_provider(cfg: &Config, name: &str, candidates: &[Provider]) -> Result<usize> {
+    let mut stdout = io::stdout();
+    writeln!(
+        stdout,
+        ":: There are {} providers available for {name}:",
+        candidates.len()
+    )?;
+    let mut repo: Option<Option<&str>> = None;
+    for (i, p) in candidates.iter().enumerate() {
+        if repo != Some(p.repo.as_deref()) {
+            repo = Some(p.repo.as_deref());
+            writeln!(
+                stdout,
+                ":: Repository {}",
+                p.repo.as_deref().unwrap_or("AUR")
+            )?;
+        }
+        writeln!(stdout, "   {}) {} {}", i + 1, p.name, p.version)?;
+    }
+
+    loop {
+        write!(stdout, "\nEnter a number (default=1): ")?;
+        if cfg.noconfirm {
+            writeln!(stdout)?;
+            return Ok(0);
+        }
+        stdout.flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(0);
+        }
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(0);
+        }
+        match input.parse::<usize>() {
+            Ok(n) if (1..=candidates.len()).contains(&n) => return Ok(n - 1),
+            _ => eprintln!("error: invalid number: {input}"),
+        }
+    }
+}
+
+/// `YAORUST_PROVIDERS="java-runtime=jre-openjdk,sh=bash"`
+pub fn parse_preferences(s: &str) -> Vec<(String, String)> {
+    s.split([',', ' ', '\n'])
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .filter(|(k, v)| !k.is_empty() && !v.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_preferences() {
+        assert_eq!(
+            parse_preferences("java-runtime=jre-openjdk, sh=bash,bogus"),
+            [
+                ("java-runtime".to_string(), "jre-openjdk".to_string()),
+                ("sh".to_string(), "bash".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Command-line targets of `-S`: plain names, `repo/pkg`, `aur/pkg`, package
+//! groups and provided (virtual) names.
+//!
+//! pacman accepts all of these, so we have to as well before deciding what
+//! goes to `pacman -S` and what is built from the AUR. Groups are expanded
+//! here, with pacman's member selection, so the plan shows real packages.
+
+use anyhow::{Result, bail};
+use std::io::{self, Write};
+
+use crate::aur::AurSource;
+use crate::config::Config;
+use crate::error::YaoError;
+use crate::pacman::PackageDb;
+use crate::pacman::db::DbPkg;
+use crate::providers::Providers;
+use crate::ui::search::parse_selection;
+
+/// Where a package comes from.
+#[derive(Debug, Clone, Copy)]
+pub enum PkgKind {
+    Repo,
+    Aur,
+    /// A sync repo group (never a dependency)
+    Group,
+}
+
+/// One package to install.
+#[derive(Debug, Clone)]
+pub struct Target {
+    /// What pacman or the AUR path gets; `repo/pkg` stays qualified
+    pub name: String,
+    /// Bare package name (installed checks, display)
+    pub pkgname: String,
+    /// Version that would be installed (for `--needed`)
+    pub version: String,
+    /// Repo or Aur; groups are expanded to their members
+    pub kind: PkgKind,
+}
+
+impl Target {
+    fn repo(repo: Option<&str>, pkgname: &str, version: &str) -> Self {
+        Self {
+            name: match repo {
+                Some(r) => format!("{r}/{pkgname}"),
+                None => pkgname.to_string(),
+            },
+            pkgname: pkgname.to_string(),
+            version: version.to_string(),
+            kind: PkgKind::Repo,
+        }
+    }
+
+    fn aur(pkgname: &str, version: &str) -> Self {
+        Self {
+            name: pkgname.to_string(),
+            pkgname: pkgname.to_string(),
+            version: version.to_string(),
+            kind: PkgKind::Aur,
+        }
+    }
+}
+
+/// Turn one command-line argument into the packages it stands for.
+pub fn resolve(
+    cfg: &Config,
+    db: &dyn PackageDb,
+    aur: &dyn AurSource,
+    providers: &Providers,
+    arg: &str,
+) -> Result<Vec<Target>> {
+    match arg.split_once('/') {
+        Some(("aur", name)) => match aur.info(name)? {
+            Some(p) => Ok(vec![Target::aur(name, &p.version)]),
+            None => bail!(YaoError::NotFound(format!("{name} not found in AUR"))),
+        },
+        Some((repo, name)) => {
+            if let Some((_, p)) = db.sync_pkgs().find(|(r, p)| *r == repo && p.name == name) {
+                return Ok(vec![Target::repo(Some(repo), name, &p.version)]);
+            }
+            let members: Vec<(&str, &DbPkg)> = db
+                .group_members(name)
+                .into_iter()
+                .filter(|(r, _)| *r == repo)
+                .collect();
+            if members.is_empty() {
+                bail!(YaoError::NotFound(format!(
+                    "{name} not found in repo {repo}"
+                )));
+            }
+            select_group(cfg, name, &members)
+        }
+        None => match classify(db, aur, arg) {
+            Ok(PkgKind::Repo) => {
+                let version = db.sync(arg).map(|(_, p)| p.version.clone());
+                Ok(vec![Target::repo(None, arg, &version.unwrap_or_default())])
+            }
+            Ok(PkgKind::Aur) => {
+                let version = aur.info(arg)?.map(|p| p.version);
+                Ok(vec![Target::aur(arg, &version.unwrap_or_default())])
+            }
+            Ok(PkgKind::Group) => select_group(cfg, arg, &db.group_members(arg)),
+            Err(e) => {
+                let Some(p) = providers.choose(arg)? else {
+                    return Err(e);
+                };
+                eprintln!("==> {arg} is provided by {}", p.name);
+                Ok(vec![match p.repo.as_deref() {
+                    Some(repo) => Target::repo(Some(repo), &p.name, &p.version),
+                    None => Target::aur(&p.name, &p.version),
+                }])
+            }
+        },
+    }
+}
+
+/// Where a bare name comes from, in pacman's order: sync package, sync
+/// group, then the AUR. Provided names and `repo/` prefixes are handled by
+/// [`resolve`].
+pub fn classify(db: &dyn PackageDb, aur: &dyn AurSource, name: &str) -> Result<PkgKind> {
+    if db.sync(name).is_some() {
+        return Ok(PkgKind::Repo);
+    }
+    if !db.group_members(name).is_empty() {
+        return Ok(PkgKind::Group);
+    }
+    if aur.exists(name)? {
+        return Ok(PkgKind::Aur);
+    }
+    bail!(YaoError::NotFound(format!(
+        "{name} not found in repos or AUR"
+    )));
+}
+
+/// pacman's group prompt: members listed per repo, empty input (or
+/// --noconfirm) takes all.
+fn select_group(cfg: &Config, group: &str, members: &[(&str, &DbPkg)]) -> Result<Vec<Target>> {
+    let mut stdout = io::stdout();
+    writeln!(
+        stdout,
+        ":: There are {} members in group {group}:",
+        members.len()
+    )?;
+    let mut repo = None;
+    let mut line = String::new();
+    for (i, (r, p)) in members.iter().enumerate() {
+        if repo != Some(*r) {
+            if !line.is_empty() {
+                writeln!(stdout, "{line}")?;
+                line.clear();
+            }
+            repo = Some(*r);
+            writeln!(stdout, ":: Repository {r}")?;
+        }
+        line.push_str(&format!("   {}) {}", i + 1, p.name));
+    }
+    writeln!(stdout, "{line}")?;
+
+    let target = |i: usize| {
+        let (repo, p) = members[i];
+        Target::repo(Some(repo), &p.name, &p.version)
+    };
+    loop {
+        write!(stdout, "\nEnter a selection (default=all): ")?;
+        let mut input = String::new();
+        if cfg.noconfirm {
+            writeln!(stdout)?;
+        } else {
+            stdout.flush()?;
+            io::stdin().read_line(&mut input)?;
+        }
+        match group_selection(&input, members.len()) {
+            Ok(picked) => return Ok(picked.into_iter().map(target).collect()),
+            Err(e) => eprintln!("error: {e}"),
+        }
+    }
+}
+
+/// Like a menu selection, but nothing entered means every member.
+fn group_selection(input: &str, count: usize) -> Result<Vec<usize>> {
+    if input.trim().is_empty() {
+        return Ok((0..count).collect());
+    }
+    let picked = parse_selection(input, count)?;
+    if picked.is_empty() {
+        bail!("nothing selected");
+    }
+    Ok(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_selection_defaults_to_all() {
+        assert_eq!(group_selection("\n", 3).unwrap(), [0, 1, 2]);
+        assert_eq!(group_selection("^2", 3).unwrap(), [0, 2]);
+        assert_eq!(group_selection("1 3", 3).unwrap(), [0, 2]);
+        assert!(group_selection("4", 3).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! `-Si` output for AUR packages, in pacman's field layout.
+
+use anyhow::Result;
+use std::io::{self, Write};
+
+use crate::aur::AurPkg;
+
+/// Label column width used by `pacman -Si` ("Depends On      : ").
+const LABEL_WIDTH: usize = 15;
+
+/// Print an AUR package the way `pacman -Si` prints a sync package.
+pub fn print_aur_info(pkg: &AurPkg) -> Result<()> {
+    let mut out = io::stdout().lock();
+    let none = || "None".to_string();
+
+    let mut field = |label: &str, value: String| writeln!(out, "{label:<LABEL_WIDTH$} : {value}");
+
+    field("Repository", "aur".to_string())?;
+    field("Name", pkg.name.clone())?;
+    if let Some(base) = pkg.package_base.as_ref().filter(|b| **b != pkg.name) {
+        field("Package Base", base.clone())?;
+    }
+    field("Version", pkg.version.clone())?;
+    field("Description", pkg.description.clone().unwrap_or_else(none))?;
+    field("URL", pkg.url.clone().unwrap_or_else(none))?;
+    field("Licenses", join(&pkg.license))?;
+    field("Keywords", join(&pkg.keywords))?;
+    field("Provides", join(&pkg.provides))?;
+    field("Depends On", join(&pkg.depends))?;
+    field("Optional Deps", optdeps(&pkg.opt_depends))?;
+    field("Make Deps", join(&pkg.make_depends))?;
+    field("Check Deps", join(&pkg.check_depends))?;
+    field("Conflicts With", join(&pkg.conflicts))?;
+    field("Replaces", join(&pkg.replaces))?;
+    field("Maintainer", pkg.maintainer.clone().unwrap_or_else(none))?;
+    field("Votes", pkg.num_votes.to_string())?;
+    field("Popularity", format!("{:.2}", pkg.popularity))?;
+    field(
+        "Out Of Date",
+        match pkg.out_of_date {
+            Some(t) => format!("Yes ({})", fmt_time(t)),
+            None => "No".to_string(),
+        },
+    )?;
+    field("First Submitted", fmt_time(pkg.first_submitted))?;
+    field("Last Modified", fmt_time(pkg.last_modified))?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// pacman joins list fields with two spaces and prints "None" when empty.
+fn join(v: &[String]) -> String {
+    if v.is_empty() {
+        "None".to_string()
+    } else {
+        v.join("  ")
+    }
+}
+
+/// Optional deps go one per line, aligned under the value column.
+fn optdeps(v: &[String]) -> String {
+    if v.is_empty() {
+        return "None".to_string();
+    }
+    v.join(&format!("\n{:width$}", "", width = LABEL_WIDTH + 3))
+}
+
+/// Format a Unix timestamp as `Sat 13 Jan 2024 17:12:31 UTC`.
+fn fmt_time(ts: i64) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let days = ts.div_euclid(86_400);
+    let secs = ts.rem_euclid(86_400);
+
+    // civil-from-days (Howard Hinnant), proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{} {:02} {} {} {:02}:{:02}:{:02} UTC",
+        DAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_unix_time_as_utc() {
+        assert_eq!(fmt_time(0), "Thu 01 Jan 1970 00:00:00 UTC");
+        assert_eq!(fmt_time(1_705_165_951), "Sat 13 Jan 2024 17:12:31 UTC");
+        assert_eq!(fmt_time(951_782_400), "Tue 29 Feb 2000 00:00:00 UTC");
+    }
+}
@@ -0,0 +1,151 @@
+//! Terminal interaction: prompts, the search menu, `-Si` output, PKGBUILD
+//! review, and running commands with their output passed through.
+
+pub mod info;
+pub mod review;
+pub mod search;
+
+use anyhow::{Result, bail};
+use std::env;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus, Stdio};
+
+use crate::config::Config;
+use crate::error::YaoError;
+
+/// Simple [Y/n] prompt on stdin.
+pub fn prompt_yes_no(cfg: &Config, prompt: &str) -> Result<bool> {
+    prompt_yes_no_default(cfg, prompt, true)
+}
+
+/// Yes/no prompt where an empty answer (or --noconfirm) means `default`.
+pub fn prompt_yes_no_default(cfg: &Config, prompt: &str, default: bool) -> Result<bool> {
+    let mut stdout = io::stdout();
+    write!(stdout, "{prompt}")?;
+    if cfg.noconfirm {
+        writeln!(stdout)?;
+        return Ok(default);
+    }
+    stdout.flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let answer = input.trim().to_lowercase();
+    if answer.is_empty() {
+        Ok(default)
+    } else {
+        Ok(answer == "y" || answer == "yes")
+    }
+}
+
+/// Pick editor for PKGBUILD:
+/// config `editor` (or YAORUST_EDITOR) > VISUAL > EDITOR > interactive with default "nano".
+pub fn choose_editor(cfg: &Config) -> Result<String> {
+    if let Some(e) = &cfg.editor {
+        return Ok(e.clone());
+    }
+    for var in ["VISUAL", "EDITOR"] {
+        if let Ok(e) = env::var(var)
+            && !e.trim().is_empty()
+        {
+            return Ok(e);
+        }
+    }
+
+    let default = "nano";
+    let mut stdout = io::stdout();
+    write!(stdout, ":: Editor to use for PKGBUILD [{}]: ", default)?;
+    stdout.flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let choice = input.trim();
+    let ed = if choice.is_empty() {
+        default.to_string()
+    } else {
+        choice.to_string()
+    };
+    Ok(ed)
+}
+
+/// Generic runner: any non-zero status is treated as an error.
+pub fn run_command_printing(cmd: &mut Command, verbose: bool) -> Result<()> {
+    let status = run_passthrough(cmd, verbose)?;
+    if !status.success() {
+        bail!("command failed with status {status}");
+    }
+    Ok(())
+}
+
+/// Variant used for pacman calls, failing with [`YaoError::Install`]. pacman
+/// exits 1 both for errors and when the user answers "n" to its own prompt,
+/// without telling the two apart, so a declined pacman prompt is an install
+/// failure too; our own prompts are what report [`YaoError::Aborted`].
+pub fn run_pacman(cmd: &mut Command, verbose: bool) -> Result<()> {
+    let status = run_passthrough(cmd, verbose)?;
+    if !status.success() {
+        bail!(YaoError::Install(format!(
+            "command failed with status {status}"
+        )));
+    }
+    Ok(())
+}
+
+/// Run `cmd` with its stdout and stderr copied to ours; returns its status.
+fn run_passthrough(cmd: &mut Command, verbose: bool) -> Result<ExitStatus> {
+    if verbose {
+        eprintln!("$ {}", pretty_cmd(cmd));
+    }
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    let mut out = child.stdout.take().unwrap();
+    let mut err = child.stderr.take().unwrap();
+    let mut stdout = io::stdout();
+    let mut stderr = io::stderr();
+
+    let t1 = std::thread::spawn(move || {
+        io::copy(&mut out, &mut stdout).ok();
+    });
+    let t2 = std::thread::spawn(move || {
+        io::copy(&mut err, &mut stderr).ok();
+    });
+
+    let status = child.wait()?;
+    let _ = t1.join();
+    let _ = t2.join();
+    Ok(status)
+}
+
+pub fn pretty_cmd(cmd: &Command) -> String {
+    let prog = cmd.get_program().to_string_lossy().to_string();
+    let args = cmd
+        .get_args()
+        .map(shell_escape)
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{prog} {args}")
+}
+
+pub fn shell_escape<S: AsRef<OsStr>>(s: S) -> String {
+    let s = s.as_ref().to_string_lossy();
+    if s.chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./=:".contains(c))
+    {
+        s.into_owned()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pacman_exit_1_is_an_install_failure() {
+        let err = run_pacman(&mut Command::new("false"), false).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(YaoError::Install(_))));
+        assert!(run_pacman(&mut Command::new("true"), false).is_ok());
+    }
+}
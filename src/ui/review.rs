@@ -0,0 +1,232 @@
+//! PKGBUILD review: diff against the last version the user approved.
+//!
+//! Every tree the user reviewed is copied to `{cache}/approved/{name}`. The
+//! next time the package is built only the changes since that copy are shown
+//! (PKGBUILD, install scripts, patches), instead of the whole PKGBUILD again.
+
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use which::which;
+
+use super::{choose_editor, prompt_yes_no};
+use crate::config::Config;
+
+/// Generated from the PKGBUILD; showing it would only double every diff.
+const IGNORED: &[&str] = &[".SRCINFO"];
+
+/// Review the tree in `build_dir` before it is built. Returns false if the
+/// user aborted. The tree is recorded as approved once the user has looked
+/// at it or accepted the diff; --noconfirm or declining the first full view
+/// keep the old baseline, so the next review still covers these changes.
+pub fn review(cfg: &Config, name: &str, build_dir: &Path) -> Result<bool> {
+    let approved = approved_dir(cfg, name);
+    let pkgbuild = build_dir.join("PKGBUILD");
+
+    if cfg.noconfirm {
+        if cfg.verbose {
+            eprintln!("==> {name}: skipping PKGBUILD review (--noconfirm)");
+        }
+        return Ok(true);
+    } else if !approved.is_dir() {
+        // First build of this package: offer the full PKGBUILD
+        if !pkgbuild.is_file() {
+            if cfg.verbose {
+                eprintln!("==> No PKGBUILD found in {}", build_dir.display());
+            }
+            return Ok(true);
+        }
+        if !prompt_yes_no(cfg, ":: View PKGBUILD? [Y/n] ")? {
+            return Ok(true);
+        }
+        if !open_editor(cfg, &pkgbuild)? {
+            return Ok(false);
+        }
+    } else {
+        let changed = changed_files(&approved, build_dir)?;
+        if changed.is_empty() {
+            eprintln!("==> {name}: no changes since the last approved PKGBUILD");
+            return Ok(true);
+        }
+        eprintln!(
+            ":: {name}: {} file(s) changed since the last approved version: {}",
+            changed.len(),
+            changed.join(", ")
+        );
+        print_diff(&cfg.diff, &approved, build_dir, &changed)?;
+
+        loop {
+            match prompt_choice(":: [v]iew full PKGBUILD, [c]ontinue, [a]bort? [v/C/a] ")? {
+                'v' => {
+                    if !open_editor(cfg, &pkgbuild)? {
+                        return Ok(false);
+                    }
+                    break;
+                }
+                'a' => {
+                    eprintln!(":: Aborted by user.");
+                    return Ok(false);
+                }
+                'c' => break,
+                _ => continue,
+            }
+        }
+    }
+
+    if let Err(e) = record_approved(build_dir, &approved)
+        && cfg.verbose
+    {
+        eprintln!("==> could not record approved PKGBUILD for {name}: {e}");
+    }
+    Ok(true)
+}
+
+fn approved_dir(cfg: &Config, name: &str) -> PathBuf {
+    cfg.snapshot_cache
+        .parent()
+        .unwrap_or(&cfg.snapshot_cache)
+        .join("approved")
+        .join(name)
+}
+
+/// Open `path` in the user's editor; false if the editor failed.
+fn open_editor(cfg: &Config, path: &Path) -> Result<bool> {
+    let editor = choose_editor(cfg)?;
+    eprintln!("==> Opening {} with {}", path.display(), editor);
+    let status = Command::new(&editor).arg(path).status()?;
+    if !status.success() {
+        eprintln!(":: Aborted by user (editor).");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Single-letter answer, lowercased; empty input picks the default (`c`).
+fn prompt_choice(prompt: &str) -> Result<char> {
+    let mut stdout = io::stdout();
+    write!(stdout, "{prompt}")?;
+    stdout.flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_lowercase().chars().next().unwrap_or('c'))
+}
+
+/// `diff -uN` of each changed file between the approved copy and the new tree.
+fn print_diff(diff: &str, old: &Path, new: &Path, files: &[String]) -> Result<()> {
+    let Ok(diff) = which(diff) else {
+        eprintln!("==> diff(1) not found; install diffutils to see the changes");
+        return Ok(());
+    };
+    for file in files {
+        let mut cmd = Command::new(&diff);
+        cmd.args(["-uN", "--color=auto"])
+            .arg("--label")
+            .arg(format!("a/{file}"))
+            .arg("--label")
+            .arg(format!("b/{file}"))
+            .arg(old.join(file))
+            .arg(new.join(file));
+        // exit status 1 just means "differs"
+        if cmd.status()?.code() == Some(2) {
+            bail!("diff failed for {file}");
+        }
+    }
+    Ok(())
+}
+
+/// Relative paths of files added, removed or modified between two trees.
+fn changed_files(old: &Path, new: &Path) -> Result<Vec<String>> {
+    let (old, new) = (read_tree(old)?, read_tree(new)?);
+    let mut changed: Vec<String> = new
+        .iter()
+        .filter(|(path, data)| old.get(*path) != Some(data))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(old.keys().filter(|p| !new.contains_key(*p)).cloned());
+    changed.sort();
+    Ok(changed)
+}
+
+/// Regular files below `root` (relative path -> contents), minus `IGNORED`.
+fn read_tree(root: &Path) -> Result<BTreeMap<String, Vec<u8>>> {
+    fn walk(root: &Path, dir: &Path, out: &mut BTreeMap<String, Vec<u8>>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let ty = entry.file_type()?;
+            if ty.is_dir() {
+                walk(root, &path, out)?;
+            } else if ty.is_file() {
+                let rel = path.strip_prefix(root)?.to_string_lossy().into_owned();
+                if !IGNORED.contains(&rel.as_str()) {
+                    out.insert(rel, fs::read(&path)?);
+                }
+            }
+        }
+        Ok(())
+    }
+    let mut out = BTreeMap::new();
+    walk(root, root, &mut out)?;
+    Ok(out)
+}
+
+/// Replace the approved copy with the tree that is about to be built.
+fn record_approved(build_dir: &Path, approved: &Path) -> Result<()> {
+    if approved.exists() {
+        fs::remove_dir_all(approved)?;
+    }
+    for (rel, data) in read_tree(build_dir)? {
+        let dst = approved.join(rel);
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(dst, data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_files_since_approval() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (build, approved) = (tmp.path().join("build"), tmp.path().join("approved"));
+        fs::create_dir_all(build.join("patches")).unwrap();
+        fs::write(build.join("PKGBUILD"), "pkgver=1\n").unwrap();
+        fs::write(build.join(".SRCINFO"), "pkgver = 1\n").unwrap();
+        fs::write(build.join("foo.install"), "post_install() { :; }\n").unwrap();
+        fs::write(build.join("patches/a.patch"), "a\n").unwrap();
+
+        record_approved(&build, &approved).unwrap();
+        assert!(changed_files(&approved, &build).unwrap().is_empty());
+        assert!(!approved.join(".SRCINFO").exists());
+
+        fs::write(build.join("PKGBUILD"), "pkgver=2\n").unwrap();
+        fs::write(build.join(".SRCINFO"), "pkgver = 2\n").unwrap();
+        fs::remove_file(build.join("foo.install")).unwrap();
+        fs::write(build.join("patches/b.patch"), "b\n").unwrap();
+        assert_eq!(
+            changed_files(&approved, &build).unwrap(),
+            ["PKGBUILD", "foo.install", "patches/b.patch"]
+        );
+    }
+
+    #[test]
+    fn unreviewed_tree_is_not_approved() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let build = tmp.path().join("build");
+        fs::create_dir_all(&build).unwrap();
+        fs::write(build.join("PKGBUILD"), "pkgver=1\n").unwrap();
+        let mut cfg = Config::default();
+        cfg.noconfirm = true;
+        cfg.snapshot_cache = tmp.path().join("snapshots");
+        assert!(review(&cfg, "foo", &build).unwrap());
+        assert!(!approved_dir(&cfg, "foo").exists());
+    }
+}
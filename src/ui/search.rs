@@ -0,0 +1,291 @@
+//! Repo + AUR search (`-Ss`) and the yaourt-style numbered selection menu.
+
+use anyhow::{Result, bail};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use crate::aur::AurSource;
+use crate::config::Config;
+use crate::pacman::PackageDb;
+use crate::pacman::db::PacmanDb;
+
+/// One search result, from a sync repo or the AUR.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    /// Sync repo name, or "aur"
+    pub repo: String,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    /// Installed version, if any
+    pub installed: Option<String>,
+    /// AUR votes/popularity (None for repo hits)
+    pub votes: Option<(u32, f64)>,
+    pub out_of_date: bool,
+}
+
+impl SearchHit {
+    pub fn is_aur(&self) -> bool {
+        self.votes.is_some()
+    }
+}
+
+/// Search the sync repos (`pacman -Ss`) and the AUR; repo hits come first.
+pub fn search_all(cfg: &Config, aur: &dyn AurSource, terms: &[String]) -> Result<Vec<SearchHit>> {
+    let mut hits = repo_search(&cfg.pacman, terms)?;
+    hits.extend(aur_search_hits(cfg, aur, terms)?);
+    Ok(hits)
+}
+
+/// Run `pacman -Ss` and parse its two-line-per-package output.
+fn repo_search(pacman: &str, terms: &[String]) -> Result<Vec<SearchHit>> {
+    let output = Command::new(pacman)
+        .arg("-Ss")
+        .arg("--")
+        .args(terms)
+        .stderr(Stdio::null())
+        .output()?;
+    // exit code 1 just means "no matches"
+    if !output.status.success() && !output.stdout.is_empty() {
+        bail!("pacman -Ss failed");
+    }
+    Ok(parse_pacman_ss(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse lines like
+/// `extra/foo 1.2-1 (group) [installed: 1.1-1]` + indented description.
+fn parse_pacman_ss(out: &str) -> Vec<SearchHit> {
+    let mut hits: Vec<SearchHit> = Vec::new();
+    for line in out.lines() {
+        if line.starts_with(char::is_whitespace) {
+            if let Some(last) = hits.last_mut() {
+                last.description = line.trim().to_string();
+            }
+            continue;
+        }
+        let mut it = line.split_whitespace();
+        let (Some(full), Some(version)) = (it.next(), it.next()) else {
+            continue;
+        };
+        let Some((repo, name)) = full.split_once('/') else {
+            continue;
+        };
+        let installed = line.find("[installed").map(|i| {
+            let marker = line[i..].trim_end_matches(']');
+            match marker.split_once(": ") {
+                Some((_, v)) => v.trim_end_matches(']').to_string(),
+                None => version.to_string(),
+            }
+        });
+        hits.push(SearchHit {
+            repo: repo.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            description: String::new(),
+            installed,
+            votes: None,
+            out_of_date: false,
+        });
+    }
+    hits
+}
+
+/// AUR search by name/description. The RPC only takes one argument, so we
+/// query with the longest term and filter by the others locally.
+fn aur_search_hits(cfg: &Config, aur: &dyn AurSource, terms: &[String]) -> Result<Vec<SearchHit>> {
+    let Some(query) = terms.iter().max_by_key(|t| t.len()) else {
+        return Ok(Vec::new());
+    };
+    let lower: Vec<String> = terms.iter().map(|t| t.to_lowercase()).collect();
+    let db = PacmanDb::open(cfg)?;
+    let installed: HashMap<String, String> = db
+        .foreign()
+        .into_iter()
+        .map(|p| (p.name.clone(), p.version.clone()))
+        .collect();
+
+    let mut pkgs = aur.search(query)?;
+    pkgs.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(pkgs
+        .into_iter()
+        .filter(|p| {
+            let hay = format!(
+                "{} {}",
+                p.name,
+                p.description.as_deref().unwrap_or_default()
+            )
+            .to_lowercase();
+            lower.iter().all(|t| hay.contains(t.as_str()))
+        })
+        .map(|p| SearchHit {
+            repo: "aur".to_string(),
+            installed: installed.get(&p.name).cloned(),
+            description: p.description.unwrap_or_default(),
+            votes: Some((p.num_votes, p.popularity)),
+            out_of_date: p.out_of_date.is_some(),
+            name: p.name,
+            version: p.version,
+        })
+        .collect())
+}
+
+/* ---------------------- Output ---------------------- */
+
+struct Colors {
+    on: bool,
+}
+
+impl Colors {
+    fn detect() -> Self {
+        Self {
+            on: io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+
+    fn paint(&self, code: &str, s: &str) -> String {
+        if self.on {
+            format!("\x1b[{code}m{s}\x1b[0m")
+        } else {
+            s.to_string()
+        }
+    }
+}
+
+/// Print hits like `pacman -Ss`, optionally prefixed with menu numbers (1-based).
+pub fn print_hits(hits: &[SearchHit], numbered: bool) -> Result<()> {
+    let c = Colors::detect();
+    let mut out = io::stdout().lock();
+    for (i, h) in hits.iter().enumerate() {
+        if numbered {
+            write!(out, "{} ", c.paint("1;33", &(i + 1).to_string()))?;
+        }
+        let repo_color = if h.is_aur() { "1;35" } else { "1;34" };
+        write!(
+            out,
+            "{}{}{} {}",
+            c.paint(repo_color, &h.repo),
+            c.paint(repo_color, "/"),
+            c.paint("1", &h.name),
+            c.paint("1;32", &h.version)
+        )?;
+        match &h.installed {
+            Some(v) if *v == h.version => write!(out, " {}", c.paint("1;36", "[installed]"))?,
+            Some(v) => write!(out, " {}", c.paint("1;36", &format!("[installed: {v}]")))?,
+            None => {}
+        }
+        if let Some((votes, pop)) = h.votes {
+            write!(out, " {}", c.paint("1;33", &format!("(+{votes} {pop:.2})")))?;
+        }
+        if h.out_of_date {
+            write!(out, " {}", c.paint("1;31", "(Out of Date)"))?;
+        }
+        writeln!(out)?;
+        writeln!(out, "    {}", h.description)?;
+    }
+    Ok(())
+}
+
+/// Ask which of `count` numbered entries to install; returns 0-based indices.
+/// With --noconfirm nothing is picked.
+pub fn prompt_selection(cfg: &Config, count: usize) -> Result<Vec<usize>> {
+    let mut stdout = io::stdout();
+    writeln!(
+        stdout,
+        "==> Enter n° of packages to be installed (ex: 1 2 3, 1-3 or ^4)"
+    )?;
+    writeln!(stdout, "==> {}", "-".repeat(60))?;
+    write!(stdout, "==> ")?;
+    if cfg.noconfirm {
+        writeln!(stdout)?;
+        return Ok(Vec::new());
+    }
+    stdout.flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    parse_selection(&input, count)
+}
+
+/// Parse a menu selection: numbers, ranges (`1-3`) and exclusions (`^4`, `^2-3`).
+/// A selection made only of exclusions starts from "everything".
+pub fn parse_selection(input: &str, count: usize) -> Result<Vec<usize>> {
+    let mut include = BTreeSet::new();
+    let mut exclude = BTreeSet::new();
+
+    for tok in input.split(|c: char| c.is_whitespace() || c == ',') {
+        if tok.is_empty() {
+            continue;
+        }
+        let (set, tok) = match tok.strip_prefix('^') {
+            Some(rest) => (&mut exclude, rest),
+            None => (&mut include, tok),
+        };
+        let (lo, hi) = match tok.split_once('-') {
+            Some((a, b)) => (parse_index(a, count)?, parse_index(b, count)?),
+            None => {
+                let n = parse_index(tok, count)?;
+                (n, n)
+            }
+        };
+        if lo > hi {
+            bail!("invalid range {tok}");
+        }
+        set.extend(lo..=hi);
+    }
+
+    if include.is_empty() && !exclude.is_empty() {
+        include.extend(1..=count);
+    }
+    Ok(include.difference(&exclude).map(|n| n - 1).collect())
+}
+
+fn parse_index(s: &str, count: usize) -> Result<usize> {
+    let n: usize = match s.trim().parse() {
+        Ok(n) => n,
+        Err(_) => bail!("invalid selection '{s}'"),
+    };
+    if n == 0 || n > count {
+        bail!("selection {n} is out of range (1-{count})");
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_numbers_ranges_and_exclusions() {
+        assert_eq!(parse_selection("1 3", 5).unwrap(), vec![0, 2]);
+        assert_eq!(parse_selection("1-3 5", 5).unwrap(), vec![0, 1, 2, 4]);
+        assert_eq!(parse_selection("1-5 ^4", 5).unwrap(), vec![0, 1, 2, 4]);
+        assert_eq!(parse_selection("^2-4", 5).unwrap(), vec![0, 4]);
+        assert_eq!(parse_selection("2,2, 1", 5).unwrap(), vec![0, 1]);
+        assert!(parse_selection("", 5).unwrap().is_empty());
+        assert!(parse_selection("6", 5).is_err());
+        assert!(parse_selection("3-1", 5).is_err());
+        assert!(parse_selection("x", 5).is_err());
+    }
+
+    #[test]
+    fn pacman_ss_output() {
+        let out = "\
+core/linux 6.6.1.arch1-1 [installed]
+    The Linux kernel and modules
+extra/foo 2.0-1 (foo-group bar) [installed: 1.9-1]
+    Foo tool
+extra/bar 0.1-3
+    Bar
+";
+        let hits = parse_pacman_ss(out);
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].repo, "core");
+        assert_eq!(hits[0].installed.as_deref(), Some("6.6.1.arch1-1"));
+        assert_eq!(hits[1].name, "foo");
+        assert_eq!(hits[1].installed.as_deref(), Some("1.9-1"));
+        assert_eq!(hits[1].description, "Foo tool");
+        assert_eq!(hits[2].installed, None);
+    }
+}
@@ -0,0 +1,204 @@
+//! The AUR network path (RPC lookups, snapshot downloads, `-G`, `-Si`)
+//! against the local stand-in in `support`.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use crate::support::{Sandbox, stderr, stdout};
+
+#[test]
+fn getpkgbuild_saves_snapshot_tree() {
+    let sb = Sandbox::new();
+    let out = sb.run(&["-G", "foo"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(stderr(&out).contains("PKGBUILD for foo saved to ./foo"));
+
+    let pkgbuild = fs::read_to_string(sb.path("work/foo/PKGBUILD")).unwrap();
+    assert!(pkgbuild.contains("pkgver=1.2.3"));
+    assert!(sb.path("work/foo/.SRCINFO").exists());
+    assert_eq!(sb.aur.count("/rpc/?v=5&type=info"), 1);
+    assert_eq!(sb.aur.count("/cgit/aur.git/snapshot/foo.tar.gz"), 1);
+    // extracted with the configured bsdtar, nothing else run
+    let calls = sb.calls();
+    assert_eq!(calls.len(), 1, "{calls:?}");
+    assert_eq!(calls[0].tool, "bsdtar");
+    let tgz = sb.path("cache/snapshots/foo.tar.gz");
+    assert_eq!(calls[0].args[..2], ["-xzf", tgz.to_str().unwrap()]);
+}
+
+#[test]
+fn getpkgbuild_split_package_uses_pkgbase() {
+    let sb = Sandbox::new();
+    let out = sb.run(&["-G", "bar-cli", "bar-gui"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    assert!(sb.path("work/bar/PKGBUILD").exists());
+    assert!(!sb.path("work/bar-cli").exists());
+    // both names in one multiinfo request, the shared tree downloaded once
+    let info = sb.aur.requests();
+    let info: Vec<&String> = info.iter().filter(|r| r.contains("type=info")).collect();
+    assert_eq!(info.len(), 1, "{info:?}");
+    assert!(info[0].contains("arg%5B%5D=bar-cli") && info[0].contains("arg%5B%5D=bar-gui"));
+    assert_eq!(sb.aur.count("/cgit/aur.git/snapshot/bar.tar.gz"), 1);
+}
+
+#[test]
+fn missing_targets_are_a_usage_error() {
+    let sb = Sandbox::new();
+    for (args, msg) in [
+        (&["-G"][..], "no packages specified for -G"),
+        (&["-Si"][..], "no packages specified for -Si"),
+        (&["-Ss"][..], "no search terms specified for -Ss"),
+    ] {
+        let out = sb.run(args);
+        assert_eq!(out.status.code(), Some(3), "{}", stderr(&out));
+        assert!(stderr(&out).contains(msg));
+    }
+    assert!(sb.calls().is_empty());
+}
+
+#[test]
+fn getpkgbuild_unknown_package_fails() {
+    let sb = Sandbox::new();
+    let out = sb.run(&["-G", "nope"]);
+    assert_eq!(out.status.code(), Some(5));
+    assert!(stderr(&out).contains("nope not found in AUR"));
+    assert_eq!(sb.aur.count("/cgit/"), 0);
+}
+
+/// Run the real git in `dir`.
+fn git(dir: &Path, args: &[&str]) {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+        .args(args)
+        .output()
+        .expect("git is needed for the git backend tests");
+    assert!(out.status.success(), "git {args:?}: {}", stderr(&out));
+}
+
+/// A bare `{name}.git` under `aur-git/`, holding the snapshot fixture of the
+/// same name as one commit (or nothing if there is none).
+fn git_repo(sb: &Sandbox, name: &str) -> String {
+    let root = sb.path("aur-git");
+    let bare = root.join(format!("{name}.git"));
+    fs::create_dir_all(&bare).unwrap();
+    git(&bare, &["init", "--quiet", "--bare"]);
+
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/aur/snapshots")
+        .join(name);
+    if fixture.is_dir() {
+        let work = root.join(name);
+        git(&root, &["clone", "--quiet", bare.to_str().unwrap(), name]);
+        for file in ["PKGBUILD", ".SRCINFO"] {
+            fs::copy(fixture.join(file), work.join(file)).unwrap();
+        }
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "--quiet", "-m", "import"]);
+        git(&work, &["push", "--quiet", "origin", "HEAD"]);
+    }
+    format!("file://{}", root.display())
+}
+
+/// `yao args...` with the git backend on the real git, cloning from `url`.
+fn run_git_backend(sb: &Sandbox, url: &str, args: &[&str]) -> Output {
+    // the fake would shadow it on PATH
+    let _ = fs::remove_file(sb.path("bin/git"));
+    sb.yao(args)
+        .env("YAORUST_FETCH_BACKEND", "git")
+        .env("YAORUST_GIT", "git")
+        .env("YAORUST_AUR_GIT_URL", url)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn getpkgbuild_clones_file_git_repo() {
+    let sb = Sandbox::new();
+    let url = git_repo(&sb, "foo");
+    let out = run_git_backend(&sb, &url, &["-G", "foo"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let pkgbuild = fs::read_to_string(sb.path("work/foo/PKGBUILD")).unwrap();
+    assert!(pkgbuild.contains("pkgver=1.2.3"));
+    assert!(sb.path("cache/git/foo/.git").is_dir());
+    // the RPC still goes to the stand-in, nothing is downloaded from it
+    assert_eq!(sb.aur.count("/rpc/?v=5&type=info"), 1);
+    assert_eq!(sb.aur.count("/cgit/"), 0);
+}
+
+#[test]
+fn getpkgbuild_empty_git_repo_is_not_found() {
+    let sb = Sandbox::new();
+    // the AUR serves an empty repository for a name it does not know, e.g.
+    // a package deleted after the RPC answered
+    let url = git_repo(&sb, "foo-empty");
+    fs::rename(sb.path("aur-git/foo-empty.git"), sb.path("aur-git/foo.git")).unwrap();
+    // twice: the empty clone must not be left behind for the next run
+    for _ in 0..2 {
+        let out = run_git_backend(&sb, &url, &["-G", "foo"]);
+        assert_eq!(out.status.code(), Some(5), "{}", stderr(&out));
+        assert!(stderr(&out).contains("foo.git is an empty repository"));
+        assert!(!sb.path("work/foo").exists());
+    }
+}
+
+#[test]
+fn unreachable_aur_is_a_network_error() {
+    let sb = Sandbox::new();
+    // nothing listens on the discard port
+    let out = sb
+        .yao(&["-G", "foo"])
+        .env("YAORUST_AUR_URL", "http://127.0.0.1:9")
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(6));
+    assert!(stderr(&out).contains("cannot reach the AUR RPC"));
+}
+
+#[test]
+fn snapshot_cache_is_revalidated_over_rpc() {
+    let sb = Sandbox::new();
+    assert!(sb.run(&["-G", "foo"]).status.success());
+    fs::remove_dir_all(sb.path("work/foo")).unwrap();
+
+    // same Version/LastModified: the cached tarball is reused
+    let out = sb.run(&["-G", "foo"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(sb.path("work/foo/PKGBUILD").exists());
+    assert_eq!(sb.aur.count("/cgit/aur.git/snapshot/foo.tar.gz"), 1);
+
+    // --refresh always downloads
+    assert!(sb.run(&["-G", "--refresh", "foo"]).status.success());
+    assert_eq!(sb.aur.count("/cgit/aur.git/snapshot/foo.tar.gz"), 2);
+}
+
+#[test]
+fn info_prints_aur_record() {
+    let sb = Sandbox::new();
+    let out = sb.run(&["-Si", "foo"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    let stdout = stdout(&out);
+    assert!(stdout.contains("foo"));
+    assert!(stdout.contains("1.2.3-1"));
+    assert!(stdout.contains("A test package"));
+
+    let out = sb.run(&["-Si", "nope"]);
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("error: package 'nope' was not found"));
+}
+
+#[test]
+fn search_goes_to_configured_aur() {
+    let sb = Sandbox::new();
+    let out = sb.run(&["-Ss", "split"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    let stdout = stdout(&out);
+    assert!(stdout.contains("aur/bar-cli"));
+    assert!(stdout.contains("aur/bar-gui"));
+    assert!(!stdout.contains("aur/foo"));
+    assert_eq!(sb.aur.count("/rpc/?v=5&type=search"), 1);
+}
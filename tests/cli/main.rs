@@ -0,0 +1,6 @@
+//! End-to-end tests of the `yao` binary, one test binary for all of them so
+//! the harness in `support` is shared.
+
+mod aur;
+mod support;
+mod sync;
@@ -0,0 +1,322 @@
+//! Integration test harness: a sandboxed `yao` run against a local AUR
+//! stand-in, with recording fakes for every external tool.
+//!
+//! Each fake appends the command line it was called with (plus its working
+//! directory and the environment variables that matter) to `calls.log`, so
+//! tests can assert exactly what yao ran and in which order. A test can swap
+//! the behavior of any fake with [`Sandbox::fake`].
+
+mod server;
+
+pub use server::AurServer;
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// Tools yao is pointed at through `YAORUST_*`.
+const TOOLS: &[(&str, &str)] = &[
+    ("pacman", "YAORUST_PACMAN"),
+    ("makepkg", "YAORUST_MAKEPKG"),
+    ("bsdtar", "YAORUST_BSDTAR"),
+    ("git", "YAORUST_GIT"),
+    ("sudo", "YAORUST_SUDO"),
+];
+
+/// Helpers yao is pointed at through `YAORUST_*` without recording them:
+/// `bin/pacman-conf` knows no repos, `bin/diff` and `bin/bwrap` do not
+/// exist, so nothing of the host's is ever picked up.
+const HELPERS: &[(&str, &str)] = &[
+    ("pacman-conf", "YAORUST_PACMAN_CONF"),
+    ("diff", "YAORUST_DIFF"),
+    ("bwrap", "YAORUST_BWRAP"),
+];
+
+/// Environment recorded with every call (when set).
+const RECORDED_ENV: &[&str] = &["PKGDEST", "PKGEXT", "PACKAGER", "no_proxy"];
+
+/// Default pacman: transactions succeed without touching the local database.
+const FAKE_PACMAN: &str = "exit 0\n";
+
+/// makepkg's root check, in every fake makepkg so trust-root mode (used
+/// when the tests run as root) can patch it.
+const MAKEPKG_ROOT_CHECK: &str = r#"E_ROOT=10
+if (( EUID == 0 )); then
+	echo "Running %s as root is not allowed" >&2
+	exit $E_ROOT
+fi
+"#;
+
+/// Default makepkg: "builds" every pkgname of the .SRCINFO in the current
+/// dir into $PKGDEST (fixtures are all arch=any).
+const FAKE_MAKEPKG: &str = r#"field() { sed -n "s/^[[:space:]]*$1 = //p" .SRCINFO | head -n1; }
+ver="$(field pkgver)-$(field pkgrel)"
+epoch="$(field epoch)"
+[ -n "$epoch" ] && ver="$epoch:$ver"
+for name in $(sed -n 's/^pkgname = //p' .SRCINFO); do
+	: > "$PKGDEST/$name-$ver-any$PKGEXT"
+done
+"#;
+
+/// One recorded tool invocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub tool: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub env: Vec<(String, String)>,
+}
+
+impl Call {
+    /// `tool arg1 arg2 ...`
+    pub fn line(&self) -> String {
+        std::iter::once(self.tool.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn env(&self, var: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|(k, _)| k == var)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A throwaway home for `yao` runs: caches, PKGDEST, a pacman DBPath and a
+/// `bin/` of fakes, all pointed at an [`AurServer`] on the bundled fixtures.
+pub struct Sandbox {
+    pub dir: tempfile::TempDir,
+    pub aur: AurServer,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        let dir = tempfile::TempDir::new().unwrap();
+        let aur =
+            AurServer::start(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/aur"));
+        for sub in [
+            "bin", "db/local", "db/sync", "work", "config", "pkgdest", "tmp",
+        ] {
+            fs::create_dir_all(dir.path().join(sub)).unwrap();
+        }
+        let sb = Self { dir, aur };
+        sb.fake("pacman", FAKE_PACMAN);
+        sb.fake("makepkg", FAKE_MAKEPKG);
+        // GNU tar understands the bsdtar invocations yao makes
+        sb.fake("bsdtar", "exec tar \"$@\"\n");
+        sb.fake("git", "exit 1\n");
+        sb.fake("sudo", "exec \"$@\"\n");
+        write_script(&sb.path("bin/pacman-conf"), "#!/bin/sh\nexit 1\n");
+        sb
+    }
+
+    pub fn path(&self, rel: &str) -> PathBuf {
+        self.dir.path().join(rel)
+    }
+
+    /// (Re)write `bin/{tool}`: record the call, then run `body` (bash).
+    pub fn fake(&self, tool: &str, body: &str) {
+        let log = self.path("calls.log");
+        let mut script = format!(
+            "#!/bin/bash\n{{\n\tprintf '%s' {tool}\n\t[ $# -gt 0 ] && printf '\\t%s' \"$@\"\n\tprintf '\\n  cwd=%s\\n' \"$PWD\"\n"
+        );
+        for var in RECORDED_ENV {
+            script.push_str(&format!(
+                "\t[ -n \"${{{var}+x}}\" ] && printf '  {var}=%s\\n' \"${var}\"\n"
+            ));
+        }
+        script.push_str(&format!("}} >> '{}'\n", log.display()));
+        if tool == "makepkg" {
+            script.push_str(MAKEPKG_ROOT_CHECK);
+        }
+        script.push_str(body);
+        write_script(&self.path("bin").join(tool), &script);
+    }
+
+    /// Add a sync repo `{repo}.db` with `(name, version)` packages.
+    pub fn sync_db(&self, repo: &str, pkgs: &[(&str, &str)]) {
+        let file = fs::File::create(self.path(&format!("db/sync/{repo}.db"))).unwrap();
+        let gz = flate2::write::GzEncoder::new(file, flate2::Compression::fast());
+        let mut tar = tar::Builder::new(gz);
+        for (name, version) in pkgs {
+            let desc = format!("%NAME%\n{name}\n\n%VERSION%\n{version}\n\n");
+            let mut header = tar::Header::new_gnu();
+            header.set_size(desc.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(
+                &mut header,
+                format!("{name}-{version}/desc"),
+                desc.as_bytes(),
+            )
+            .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    /// Mark `name` as installed in the local database.
+    pub fn installed(&self, name: &str, version: &str) {
+        let dir = self.path(&format!("db/local/{name}-{version}"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("desc"),
+            format!("%NAME%\n{name}\n\n%VERSION%\n{version}\n\n%REASON%\n0\n\n"),
+        )
+        .unwrap();
+    }
+
+    /// `yao args...`, run in `work/` with stdin closed.
+    pub fn yao(&self, args: &[&str]) -> Command {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let mut paths = vec![self.path("bin")];
+        paths.extend(std::env::split_paths(&path));
+
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yao"));
+        cmd.args(args)
+            .current_dir(self.path("work"))
+            .env("PATH", std::env::join_paths(paths).unwrap())
+            .env("HOME", self.dir.path())
+            .env("TMPDIR", self.path("tmp"))
+            .env("XDG_CONFIG_HOME", self.path("config"))
+            .env("YAORUST_AUR_URL", &self.aur.url)
+            .env("YAORUST_DBPATH", self.path("db"))
+            .env("YAORUST_SNAPSHOT_CACHE", self.path("cache/snapshots"))
+            .env("YAORUST_GIT_CACHE", self.path("cache/git"))
+            // no build user to drop to; the fake makepkg is safe to run as root
+            .env("YAORUST_ROOT_MODE", "trust-root")
+            .env("PKGDEST", self.path("pkgdest"))
+            .env("PKGEXT", ".pkg.tar.zst")
+            .env("NO_PROXY", "127.0.0.1")
+            .env("no_proxy", "127.0.0.1")
+            .env_remove("YAORUST_FETCH_BACKEND")
+            .env_remove("YAORUST_PROVIDERS")
+            .stdin(Stdio::null());
+        for (tool, var) in TOOLS.iter().chain(HELPERS) {
+            cmd.env(var, self.path("bin").join(tool));
+        }
+        cmd
+    }
+
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.yao(args).output().expect("run yao")
+    }
+
+    /// `yao args...` in `RootMode::User` with `nobody` as the build user.
+    /// The sandbox is opened up so the fakes still run and record as
+    /// `nobody`; without root, yao runs makepkg directly anyway.
+    pub fn yao_as_build_user(&self, args: &[&str]) -> Command {
+        fs::set_permissions(self.dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+        let log = self.path("calls.log");
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log)
+            .unwrap();
+        fs::set_permissions(&log, fs::Permissions::from_mode(0o666)).unwrap();
+        let mut cmd = self.yao(args);
+        cmd.env("YAORUST_ROOT_MODE", "user")
+            .env("YAORUST_BUILD_USER", "nobody");
+        cmd
+    }
+
+    /// `yao args...` on a terminal (script(1) provides the pty), answering
+    /// its prompts with `input`. stdout and stderr both end up in stdout.
+    pub fn run_tty(&self, args: &[&str], input: &str) -> Output {
+        let yao = self.yao(args);
+        let line = std::iter::once(yao.get_program())
+            .chain(yao.get_args())
+            .map(|a| format!("'{}'", a.to_str().unwrap()))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut cmd = Command::new("script");
+        cmd.args(["-qec", &line, "/dev/null"])
+            .current_dir(self.path("work"))
+            .env("SHELL", "/bin/sh")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for (var, value) in yao.get_envs() {
+            match value {
+                Some(value) => cmd.env(var, value),
+                None => cmd.env_remove(var),
+            };
+        }
+        let mut child = cmd.spawn().expect("run script");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        child.wait_with_output().expect("run script")
+    }
+
+    /// Every recorded call, in order.
+    pub fn calls(&self) -> Vec<Call> {
+        let log = fs::read_to_string(self.path("calls.log")).unwrap_or_default();
+        let mut calls: Vec<Call> = Vec::new();
+        for line in log.lines() {
+            if let Some(rest) = line.strip_prefix("  ") {
+                let call = calls.last_mut().expect("call line before attributes");
+                let (key, value) = rest.split_once('=').unwrap();
+                match key {
+                    "cwd" => call.cwd = PathBuf::from(value),
+                    _ => call.env.push((key.to_string(), value.to_string())),
+                }
+                continue;
+            }
+            let mut fields = line.split('\t');
+            calls.push(Call {
+                tool: fields.next().unwrap().to_string(),
+                args: fields.map(str::to_string).collect(),
+                cwd: PathBuf::new(),
+                env: Vec::new(),
+            });
+        }
+        calls
+    }
+
+    /// The recorded command lines without the `sudo` wrapper (which is only
+    /// there when the tests do not run as root). Sandbox paths are shown as
+    /// `$SB`, yao's per-run temp dirs as `$TMP`.
+    pub fn commands(&self) -> Vec<String> {
+        self.calls()
+            .iter()
+            .filter(|c| c.tool != "sudo")
+            .map(|c| self.mask(&c.line()))
+            .collect()
+    }
+
+    pub fn mask(&self, text: &str) -> String {
+        let root = self.dir.path().display().to_string();
+        text.replace(&root, "$SB")
+            .split(' ')
+            .map(|word| match word.strip_prefix("$SB/tmp/") {
+                Some(rest) => match rest.split_once('/') {
+                    Some((_, path)) => format!("$TMP/{path}"),
+                    None => "$TMP".to_string(),
+                },
+                None => word.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn write_script(path: &Path, script: &str) {
+    fs::write(path, script).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+pub fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+pub fn stdout(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stdout).into_owned()
+}
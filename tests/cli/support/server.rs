@@ -0,0 +1,237 @@
+//! A stand-in for aur.archlinux.org on localhost.
+//!
+//! Serves RPC v5 `info` (one or many `arg[]`) and `search` (`by=name`,
+//! `name-desc`, `provides`) from `{fixtures}/rpc/*.json`, and
+//! `/cgit/aur.git/snapshot/{pkgbase}.tar.gz` packed on the fly from
+//! `{fixtures}/snapshots/{pkgbase}/`. Every request path is logged so tests
+//! can check what yao asked for.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use serde_json::{Value, json};
+
+pub struct AurServer {
+    /// `http://127.0.0.1:{port}`, to be used as `aur_url`
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AurServer {
+    pub fn start(fixtures: impl Into<PathBuf>) -> Self {
+        let fixtures = fixtures.into();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let (log, done) = (requests.clone(), stop.clone());
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if done.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let _ = serve(&fixtures, stream, &log);
+                }
+            }
+        });
+        Self {
+            url,
+            requests,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Paths (with query) of every request so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Number of requests whose path starts with `prefix`.
+    pub fn count(&self, prefix: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter(|r| r.starts_with(prefix))
+            .count()
+    }
+}
+
+impl Drop for AurServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // wake up accept()
+        let _ = TcpStream::connect(self.url.trim_start_matches("http://"));
+        if let Some(t) = self.thread.take() {
+            let _ = t.join();
+        }
+    }
+}
+
+fn serve(fixtures: &Path, mut stream: TcpStream, log: &Mutex<Vec<String>>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // drain the headers
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+    }
+    let target = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+    log.lock().unwrap().push(target.clone());
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let (status, ctype, body) = if path == "/rpc/" || path == "/rpc" {
+        let body = rpc(fixtures, &parse_query(query));
+        ("200 OK", "application/json", body.to_string().into_bytes())
+    } else if let Some(base) = path
+        .strip_prefix("/cgit/aur.git/snapshot/")
+        .and_then(|f| f.strip_suffix(".tar.gz"))
+    {
+        match snapshot(fixtures, base) {
+            Some(tgz) => ("200 OK", "application/x-gzip", tgz),
+            None => ("404 Not Found", "text/plain", b"not found".to_vec()),
+        }
+    } else {
+        ("404 Not Found", "text/plain", b"not found".to_vec())
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {ctype}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
+
+/// All fixture records, sorted by name.
+fn records(fixtures: &Path) -> Vec<Value> {
+    let mut out: Vec<Value> = fs::read_dir(fixtures.join("rpc"))
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .filter_map(|e| fs::read(e.path()).ok())
+                .filter_map(|data| serde_json::from_slice(&data).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    out.sort_by_key(|r: &Value| r["Name"].as_str().unwrap_or_default().to_string());
+    out
+}
+
+fn rpc(fixtures: &Path, params: &[(String, String)]) -> Value {
+    let get = |k: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == k)
+            .map(|(_, v)| v.as_str())
+    };
+    if get("v") != Some("5") {
+        return json!({"version": null, "type": "error", "resultcount": 0, "results": [], "error": "Invalid version specified."});
+    }
+    let all = records(fixtures);
+    let (ty, results): (&str, Vec<Value>) = match get("type") {
+        Some("info") | Some("multiinfo") => {
+            let names: Vec<&str> = params
+                .iter()
+                .filter(|(k, _)| k == "arg[]" || k == "arg")
+                .map(|(_, v)| v.as_str())
+                .collect();
+            let hits = all
+                .into_iter()
+                .filter(|r| names.contains(&r["Name"].as_str().unwrap_or_default()))
+                .collect();
+            ("multiinfo", hits)
+        }
+        Some("search") => {
+            let arg = get("arg").unwrap_or_default().to_lowercase();
+            let by = get("by").unwrap_or("name-desc");
+            let hits = all
+                .into_iter()
+                .filter(|r| {
+                    let name = r["Name"].as_str().unwrap_or_default();
+                    let desc = r["Description"].as_str().unwrap_or_default();
+                    match by {
+                        "name" => name.contains(&arg),
+                        "provides" => {
+                            name == arg
+                                || r["Provides"].as_array().is_some_and(|p| {
+                                    p.iter().filter_map(Value::as_str).any(|d| {
+                                        d.split(['=', '<', '>']).next() == Some(arg.as_str())
+                                    })
+                                })
+                        }
+                        _ => name.contains(&arg) || desc.to_lowercase().contains(&arg),
+                    }
+                })
+                .collect();
+            ("search", hits)
+        }
+        _ => {
+            return json!({"version": 5, "type": "error", "resultcount": 0, "results": [], "error": "Incorrect request type specified."});
+        }
+    };
+    json!({"version": 5, "type": ty, "resultcount": results.len(), "results": results})
+}
+
+/// `{fixtures}/snapshots/{base}/` as `{base}/...` in a .tar.gz, like cgit.
+fn snapshot(fixtures: &Path, base: &str) -> Option<Vec<u8>> {
+    let dir = fixtures.join("snapshots").join(base);
+    if base.contains('/') || !dir.is_dir() {
+        return None;
+    }
+    let gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    let mut tar = tar::Builder::new(gz);
+    tar.append_dir_all(base, &dir).ok()?;
+    tar.into_inner().ok()?.finish().ok()
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 3;
+                        continue;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,335 @@
+//! `yao -S` end to end: the exact pacman/makepkg/bsdtar command lines, their
+//! order and environment, recorded by the fakes in `support`.
+
+use std::fs;
+
+use crate::support::{Sandbox, stderr, stdout};
+
+const MAKEPKG: &str =
+    "makepkg --clean --cleanbuild --syncdeps --noconfirm --needed --log --config /etc/makepkg.conf";
+const FOO_PKG: &str = "$SB/pkgdest/foo-1.2.3-1-any.pkg.tar.zst";
+
+fn sandbox() -> Sandbox {
+    let sb = Sandbox::new();
+    sb.sync_db("core", &[("glibc", "2.40-1"), ("zlib", "1:1.3.1-2")]);
+    sb
+}
+
+fn sync(sb: &Sandbox, args: &[&str]) -> String {
+    let mut argv = vec!["-S", "--noconfirm"];
+    argv.extend(args);
+    let out = sb.run(&argv);
+    assert!(out.status.success(), "{}", stderr(&out));
+    stderr(&out)
+}
+
+/// `yao -S --noconfirm` expected to fail with exit code `code`.
+fn sync_fails(sb: &Sandbox, args: &[&str], code: i32) -> String {
+    let mut argv = vec!["-S", "--noconfirm"];
+    argv.extend(args);
+    let out = sb.run(&argv);
+    assert_eq!(out.status.code(), Some(code), "{}", stderr(&out));
+    stderr(&out)
+}
+
+#[test]
+fn repo_only_goes_straight_to_pacman() {
+    let sb = sandbox();
+    sync(&sb, &["zlib", "core/glibc"]);
+    assert_eq!(sb.commands(), ["pacman --noconfirm -S zlib core/glibc"]);
+    assert_eq!(sb.aur.count("/cgit/"), 0);
+}
+
+#[test]
+fn aur_only_fetches_builds_and_installs() {
+    let sb = sandbox();
+    sync(&sb, &["foo"]);
+    assert_eq!(
+        sb.commands(),
+        [
+            "bsdtar -xzf $SB/cache/snapshots/foo.tar.gz -C $TMP".to_string(),
+            MAKEPKG.to_string(),
+            format!("pacman --noconfirm -U {FOO_PKG}"),
+        ]
+    );
+
+    let calls = sb.calls();
+    let makepkg = calls.iter().find(|c| c.tool == "makepkg").unwrap();
+    assert_eq!(sb.mask(&makepkg.cwd.display().to_string()), "$TMP/foo");
+    assert_eq!(
+        makepkg.env("PKGDEST"),
+        Some(sb.path("pkgdest").to_str().unwrap())
+    );
+    assert!(sb.path("pkgdest/foo-1.2.3-1-any.pkg.tar.zst").exists());
+}
+
+#[test]
+fn build_user_keeps_makepkg_and_proxy_env() {
+    let sb = sandbox();
+    let out = sb
+        .yao_as_build_user(&["-S", "--noconfirm", "foo"])
+        .env("PACKAGER", "Alice <alice@example.org>")
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let calls = sb.calls();
+    let makepkg = calls.iter().find(|c| c.tool == "makepkg").unwrap();
+    assert_eq!(makepkg.env("PKGEXT"), Some(".pkg.tar.zst"));
+    assert_eq!(makepkg.env("PACKAGER"), Some("Alice <alice@example.org>"));
+    assert_eq!(makepkg.env("no_proxy"), Some("127.0.0.1"));
+    assert_eq!(
+        sb.commands().last().unwrap(),
+        &format!("pacman --noconfirm -U {FOO_PKG}")
+    );
+    assert!(sb.path("pkgdest/foo-1.2.3-1-any.pkg.tar.zst").exists());
+}
+
+#[test]
+fn aur_dependency_built_earlier_counts_as_installed() {
+    let sb = sandbox();
+    sb.installed("glibc", "2.40-1");
+    // pacman -U registers foo, which qux needs at build time
+    let local = sb.path("db/local");
+    sb.fake(
+        "pacman",
+        &format!(
+            "[ \"$2\" = -U ] || exit 0\ncase \"$*\" in *foo-1.2.3-1-any*)\n\
+             \tmkdir -p '{0}'/foo-1.2.3-1\n\
+             \tprintf '%%NAME%%\\nfoo\\n\\n%%VERSION%%\\n1.2.3-1\\n\\n' > '{0}'/foo-1.2.3-1/desc\n\
+             esac\n",
+            local.display()
+        ),
+    );
+    let out = sb
+        .yao_as_build_user(&["-S", "--noconfirm", "qux"])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(stderr(&out).contains("   foo (AUR dependency of qux)"));
+
+    let commands = sb.commands();
+    assert!(
+        !commands
+            .iter()
+            .any(|c| c.starts_with("pacman --noconfirm -S")),
+        "{commands:?}"
+    );
+    assert_eq!(
+        commands.last().unwrap(),
+        "pacman --noconfirm -U $SB/pkgdest/qux-0.1-1-any.pkg.tar.zst"
+    );
+}
+
+#[test]
+fn mixed_installs_repo_packages_first() {
+    let sb = sandbox();
+    let err = sync(&sb, &["foo", "zlib"]);
+    assert!(err.contains("   zlib (repo)"));
+    assert!(err.contains("   foo (AUR)"));
+    assert_eq!(
+        sb.commands(),
+        [
+            "pacman --noconfirm -S zlib".to_string(),
+            "bsdtar -xzf $SB/cache/snapshots/foo.tar.gz -C $TMP".to_string(),
+            MAKEPKG.to_string(),
+            format!("pacman --noconfirm -U {FOO_PKG}"),
+        ]
+    );
+}
+
+#[test]
+fn split_dependency_is_built_once_and_marked() {
+    let sb = sandbox();
+    // pacman -U registers what it installed, so yao sees bar-cli afterwards
+    let local = sb.path("db/local");
+    sb.fake(
+        "pacman",
+        &format!(
+            "[ \"$2\" = -U ] || exit 0\nfor name in bar-cli bar-gui; do\n\
+             \tmkdir -p '{0}'/$name-0.9-2\n\
+             \tprintf '%%NAME%%\\n%s\\n\\n%%VERSION%%\\n0.9-2\\n\\n' $name > '{0}'/$name-0.9-2/desc\n\
+             done\n",
+            local.display()
+        ),
+    );
+    let err = sync(&sb, &["bar-gui"]);
+    assert!(err.contains("   bar-cli (AUR dependency of bar-gui)"));
+    assert!(err.contains("==> [aur] building bar (bar-cli bar-gui)"));
+    assert_eq!(
+        sb.commands(),
+        [
+            "bsdtar -xzf $SB/cache/snapshots/bar.tar.gz -C $TMP",
+            MAKEPKG,
+            "pacman --noconfirm -U $SB/pkgdest/bar-cli-0.9-2-any.pkg.tar.zst \
+             $SB/pkgdest/bar-gui-0.9-2-any.pkg.tar.zst",
+            "pacman -D --asdeps bar-cli",
+        ]
+    );
+    assert_eq!(sb.aur.count("/cgit/aur.git/snapshot/bar.tar.gz"), 1);
+}
+
+#[test]
+fn conflict_with_a_repo_target_stops_before_anything_runs() {
+    let sb = sandbox();
+    let err = sync_fails(&sb, &["baz", "zlib"], 12);
+    assert!(
+        err.contains(
+            "unresolvable package conflicts detected: baz and zlib are in conflict (zlib)"
+        )
+    );
+    assert!(sb.commands().is_empty());
+    assert_eq!(sb.aur.count("/cgit/"), 0);
+}
+
+#[test]
+fn noconfirm_keeps_conflicting_installed_package() {
+    let sb = sandbox();
+    sb.installed("zlib", "1:1.3.1-2");
+    let err = sync_fails(&sb, &["baz"], 12);
+    assert!(err.contains("not removing zlib with --noconfirm"));
+    assert!(!err.contains("Aborted by user"));
+    assert!(sb.commands().is_empty());
+}
+
+#[test]
+fn declined_proceed_prompt_aborts() {
+    let sb = sandbox();
+    let out = sb.run_tty(&["-S", "foo", "zlib"], "n\n");
+    let text = stdout(&out);
+    assert_eq!(out.status.code(), Some(11), "{text}");
+    assert!(text.contains(":: Proceed with installation? [Y/n]"));
+    assert!(text.contains(":: Aborted by user."));
+    assert!(!text.contains("Error:"));
+    assert!(sb.commands().is_empty());
+    assert_eq!(sb.aur.count("/cgit/"), 0);
+}
+
+#[test]
+fn declined_conflict_removal_aborts() {
+    let sb = sandbox();
+    sb.installed("zlib", "1:1.3.1-2");
+    let out = sb.run_tty(&["-S", "baz"], "n\n");
+    let text = stdout(&out);
+    assert_eq!(out.status.code(), Some(11), "{text}");
+    assert!(text.contains(":: baz and zlib are in conflict. Remove zlib? [y/N]"));
+    assert!(text.contains(":: Aborted by user."));
+    assert!(!text.contains("Error:"));
+    assert!(sb.commands().is_empty());
+}
+
+#[test]
+fn accepted_diff_becomes_the_new_baseline() {
+    let sb = sandbox();
+    let approved = sb.path("cache/approved/foo");
+    fs::create_dir_all(&approved).unwrap();
+    fs::write(approved.join("PKGBUILD"), "pkgname=foo\npkgver=1.2.2\n").unwrap();
+
+    // proceed, then Enter at the review prompt
+    let out = sb.run_tty(&["-S", "foo"], "\n\n");
+    let text = stdout(&out);
+    assert!(out.status.success(), "{text}");
+    assert!(text.contains(":: foo: 1 file(s) changed since the last approved version: PKGBUILD"));
+    assert!(text.contains("[c]ontinue"));
+    let pkgbuild = fs::read_to_string(approved.join("PKGBUILD")).unwrap();
+    assert!(pkgbuild.contains("pkgver=1.2.3"));
+}
+
+#[test]
+fn existing_package_is_reused_unless_forced() {
+    let sb = sandbox();
+    fs::write(sb.path("pkgdest/foo-1.2.3-1-any.pkg.tar.zst"), "old").unwrap();
+
+    sync(&sb, &["foo"]);
+    assert_eq!(
+        sb.commands(),
+        [
+            "bsdtar -xzf $SB/cache/snapshots/foo.tar.gz -C $TMP".to_string(),
+            format!("pacman --noconfirm -U {FOO_PKG}"),
+        ]
+    );
+
+    fs::remove_file(sb.path("calls.log")).unwrap();
+    sync(&sb, &["-f", "foo"]);
+    assert_eq!(
+        sb.commands(),
+        [
+            "bsdtar -xzf $SB/cache/snapshots/foo.tar.gz -C $TMP".to_string(),
+            format!("{MAKEPKG} -f -C"),
+            format!("pacman --noconfirm -U {FOO_PKG}"),
+        ]
+    );
+    // the old file was removed before the build, the fake wrote a new one
+    let pkg = fs::read_to_string(sb.path("pkgdest/foo-1.2.3-1-any.pkg.tar.zst")).unwrap();
+    assert!(pkg.is_empty());
+}
+
+#[test]
+fn needed_skips_up_to_date_targets() {
+    let sb = sandbox();
+    sb.installed("foo", "1.2.3-1");
+    // newer than the sync db counts as up to date too
+    sb.installed("zlib", "1:1.3.2-1");
+    let err = sync(&sb, &["--needed", "foo", "zlib"]);
+    assert!(err.contains("warning: foo-1.2.3-1 is up to date -- skipping"));
+    assert!(err.contains("warning: zlib-1:1.3.2-1 is up to date -- skipping"));
+    assert!(err.contains(" there is nothing to do"));
+    assert!(sb.commands().is_empty());
+}
+
+#[test]
+fn failed_pacman_stops_the_run() {
+    let sb = sandbox();
+    // pacman's exit 1 is a failure, whether or not it could have asked
+    sb.fake("pacman", "exit 1\n");
+
+    let err = sync_fails(&sb, &["zlib"], 10);
+    assert!(!err.contains("Aborted by user"));
+    assert!(err.contains("command failed"));
+    assert_eq!(sb.commands(), ["pacman --noconfirm -S zlib"]);
+
+    // nothing is built after the repo transaction failed
+    fs::remove_file(sb.path("calls.log")).unwrap();
+    sync_fails(&sb, &["foo", "zlib"], 10);
+    assert_eq!(sb.commands(), ["pacman --noconfirm -S zlib"]);
+
+    fs::remove_file(sb.path("calls.log")).unwrap();
+    sync_fails(&sb, &["foo"], 10);
+    assert_eq!(
+        sb.commands(),
+        [
+            "bsdtar -xzf $SB/cache/snapshots/foo.tar.gz -C $TMP".to_string(),
+            MAKEPKG.to_string(),
+            format!("pacman --noconfirm -U {FOO_PKG}"),
+        ]
+    );
+}
+
+#[test]
+fn failed_build_installs_nothing() {
+    let sb = sandbox();
+    sb.fake("makepkg", "exit 4\n");
+    let err = sync_fails(&sb, &["foo"], 9);
+    assert!(err.contains("makepkg failed for foo"));
+    assert!(err.contains("command failed"));
+    let commands = sb.commands();
+    assert_eq!(commands.last().map(String::as_str), Some(MAKEPKG));
+}
+
+#[test]
+fn prompts_need_a_terminal() {
+    let sb = sandbox();
+    let out = sb.run(&["-S", "foo"]);
+    assert_eq!(out.status.code(), Some(3));
+    assert!(stderr(&out).contains("use --noconfirm"));
+    assert!(sb.calls().is_empty());
+}
+
+#[test]
+fn refresh_is_rejected_for_aur_only_upgrades() {
+    let sb = sandbox();
+    let out = sb.run(&["-Syua", "--noconfirm"]);
+    assert_eq!(out.status.code(), Some(3));
+    assert!(stderr(&out).contains("-y cannot be combined with -a"));
+    assert!(sb.calls().is_empty());
+}